@@ -5,11 +5,17 @@
 //! materials will use a particular BRDF rather than generically templating on
 //! it.
 
+use crate::btdf;
+use crate::sampler::{self, Generator, Samples};
 use crate::utility::{Colour, Vec3};
 use crate::world::Intersection;
 
 use std::f64::consts;
 
+/// Number of samples in each set of hemisphere samples used by
+/// [`BRDF::sample_f`].
+const NUM_SAMPLES: usize = 100;
+
 /// A BRDF function.
 ///
 /// All bidirectional reflectance distribution functions will implement this
@@ -20,22 +26,41 @@ pub trait BRDF {
     /// from `in_dir` in the direction `out_dir`.
     fn call(&self, hit: &Intersection, in_dir: Vec3, out_dir: Vec3) -> Colour;
 
+    /// Randomly choose an incoming direction, for light reflected in the
+    /// direction `out_dir`.
+    ///
+    /// Returns the value of the BRDF for the chosen direction, the direction
+    /// itself, and the probability density of choosing it. Directions are
+    /// chosen in proportion to the BRDF as closely as possible, for use in
+    /// Monte Carlo integration.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64);
+
     /// The bihemispherial reflectance ρ for `out_dir`
     fn rho(&self, hit: &Intersection, out_dir: Vec3) -> Colour;
 }
 
+/// Converts a sample on the hemisphere around `z` to one around `w`.
+pub(crate) fn orient(sample: Vec3, w: Vec3) -> Vec3 {
+    // jitter "up" so it's never parallel to `w`
+    let v = Vec3::new(0.0034, 1.0, 0.0071).cross(w).normalise();
+    let u = v.cross(w);
+    sample.x * u + sample.y * v + sample.z * w
+}
+
 /// Perfect diffuse reflection.
 ///
 /// This is a good approximation for dull, matte materials like paper.
 #[derive(Debug, Clone)]
 pub struct Lambertian {
     rho: Colour,
+    samples: Samples<Vec3>,
 }
 
 impl Lambertian {
     pub fn new(reflectance: f64, colour: Colour) -> Self {
         let rho = reflectance * colour;
-        Self { rho }
+        let samples = sampler::Default::new(NUM_SAMPLES).gen_hemisphere_samples(1.0);
+        Self { rho, samples }
     }
 }
 
@@ -43,6 +68,13 @@ impl BRDF for Lambertian {
     fn call(&self, _hit: &Intersection, _in_dir: Vec3, _out_dir: Vec3) -> Colour {
         self.rho * consts::FRAC_1_PI
     }
+
+    /// Directions are cosine-distributed about the normal.
+    fn sample_f(&self, hit: &Intersection, _out_dir: Vec3) -> (Colour, Vec3, f64) {
+        let in_dir = orient(self.samples.sample(hit.depth), hit.normal);
+        let pdf = hit.normal.dot(in_dir) * consts::FRAC_1_PI;
+        (self.rho * consts::FRAC_1_PI, in_dir, pdf)
+    }
     fn rho(&self, _hit: &Intersection, _out_dir: Vec3) -> Colour {
         self.rho
     }
@@ -55,15 +87,29 @@ impl BRDF for Lambertian {
 pub struct GlossySpecular {
     rho: Colour,
     exponent: f64,
+    samples: Samples<Vec3>,
 }
 
 impl GlossySpecular {
     pub fn new(reflectance: f64, shininess: f64, colour: Colour) -> Self {
+        let samples =
+            sampler::Default::new(NUM_SAMPLES).gen_hemisphere_samples(shininess);
         Self {
             rho: reflectance * colour,
             exponent: shininess,
+            samples,
         }
     }
+
+    /// Creates a specular lobe scaled so that `reflectance` is the fraction of
+    /// light reflected when lit head-on, rather than the peak of the lobe.
+    ///
+    /// This suits blurry reflections, where the lobe carries all of the
+    /// reflected light, while [`GlossySpecular::new`] suits highlights.
+    pub fn normalised(reflectance: f64, exponent: f64, colour: Colour) -> Self {
+        let scale = (exponent + 1.0) * 0.5 * consts::FRAC_1_PI;
+        Self::new(reflectance * scale, exponent, colour)
+    }
 }
 
 impl BRDF for GlossySpecular {
@@ -79,6 +125,99 @@ impl BRDF for GlossySpecular {
         }
     }
 
+    /// Directions are distributed around the mirror reflection of `out_dir`,
+    /// in proportion to the specular lobe. Any that end up below the surface
+    /// reflect nothing.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64) {
+        let n_dot_out = hit.normal.dot(out_dir);
+        let r = -out_dir + 2.0 * hit.normal * n_dot_out;
+
+        let in_dir = orient(self.samples.sample(hit.depth), r);
+        let lobe = r.dot(in_dir).max(0.0).powf(self.exponent);
+        let pdf = (self.exponent + 1.0) * 0.5 * consts::FRAC_1_PI * lobe;
+
+        if hit.normal.dot(in_dir) > 0.0 {
+            (self.rho * lobe, in_dir, pdf)
+        } else {
+            (Colour::black(), in_dir, pdf)
+        }
+    }
+
+    fn rho(&self, _hit: &Intersection, _out_dir: Vec3) -> Colour {
+        Colour::black()
+    }
+}
+
+/// Perfect specular reflection, like a mirror.
+///
+/// All light is reflected in exactly one direction, so this is a delta
+/// function: [`BRDF::call`] is always black, and reflected light can only be
+/// found through [`BRDF::sample_f`].
+#[derive(Debug, Clone)]
+pub struct PerfectSpecular {
+    rho: Colour,
+}
+
+impl PerfectSpecular {
+    pub fn new(reflectance: f64, colour: Colour) -> Self {
+        let rho = reflectance * colour;
+        Self { rho }
+    }
+}
+
+impl BRDF for PerfectSpecular {
+    fn call(&self, _hit: &Intersection, _in_dir: Vec3, _out_dir: Vec3) -> Colour {
+        Colour::black()
+    }
+
+    /// Always returns the mirror reflection of `out_dir`. Since there is only
+    /// one possible direction the PDF is `1`, and the cosine term is divided
+    /// out of the result.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64) {
+        let n_dot_out = hit.normal.dot(out_dir);
+        let in_dir = -out_dir + 2.0 * hit.normal * n_dot_out;
+        (self.rho / hit.normal.dot(in_dir), in_dir, 1.0)
+    }
+
+    fn rho(&self, _hit: &Intersection, _out_dir: Vec3) -> Colour {
+        self.rho
+    }
+}
+
+/// Reflection from a dielectric like glass or water, following the Fresnel
+/// equations.
+///
+/// This is like [`PerfectSpecular`], except the amount of light reflected
+/// depends on the angle, with the rest being transmitted (see
+/// [`FresnelTransmitter`](crate::btdf::FresnelTransmitter)).
+#[derive(Debug, Clone)]
+pub struct FresnelReflector {
+    eta_in: f64,
+    eta_out: f64,
+}
+
+impl FresnelReflector {
+    /// Creates a new reflector between a medium with index of refraction
+    /// `eta_in` on the inside of the surface, and `eta_out` on the outside.
+    pub fn new(eta_in: f64, eta_out: f64) -> Self {
+        Self { eta_in, eta_out }
+    }
+}
+
+impl BRDF for FresnelReflector {
+    fn call(&self, _hit: &Intersection, _in_dir: Vec3, _out_dir: Vec3) -> Colour {
+        Colour::black()
+    }
+
+    /// Always returns the mirror reflection of `out_dir`, as with
+    /// [`PerfectSpecular`]. This works from either side of the surface.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64) {
+        let n_dot_out = hit.normal.dot(out_dir);
+        let in_dir = -out_dir + 2.0 * hit.normal * n_dot_out;
+        let kr = btdf::fresnel(hit.normal, out_dir, self.eta_in, self.eta_out);
+        (Colour::white() * kr / n_dot_out.abs(), in_dir, 1.0)
+    }
+
     fn rho(&self, _hit: &Intersection, _out_dir: Vec3) -> Colour {
         Colour::black()
     }
@@ -0,0 +1,225 @@
+//! Bidirectional transmittance distribution functions.
+//!
+//! These are the counterparts to [BRDFs](crate::brdf) for light that passes
+//! through a surface rather than reflecting off it, and are used by
+//! transparent [materials](crate::material).
+
+use crate::utility::{Colour, Vec3};
+use crate::world::Intersection;
+
+/// A BTDF function.
+///
+/// This mirrors the [`BRDF`](crate::brdf::BRDF) trait, except that the
+/// incoming light arrives from the other side of the surface.
+pub trait BTDF {
+    /// Call the BTDF. This returns the contribution of the transmitted
+    /// irradiance from `in_dir` in the direction `out_dir`.
+    fn call(&self, hit: &Intersection, in_dir: Vec3, out_dir: Vec3) -> Colour;
+
+    /// Choose an incoming direction on the far side of the surface, for light
+    /// transmitted in the direction `out_dir`.
+    ///
+    /// Returns the value of the BTDF for the chosen direction, the direction
+    /// itself, and the probability density of choosing it.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64);
+
+    /// The bihemispherial transmittance ρ for `out_dir`
+    fn rho(&self, hit: &Intersection, out_dir: Vec3) -> Colour;
+
+    /// Whether light in the direction `out_dir` is totally internally
+    /// reflected, so that none is transmitted.
+    fn tir(&self, hit: &Intersection, out_dir: Vec3) -> bool;
+}
+
+/// Details of light crossing the boundary between two media.
+struct Crossing {
+    /// The normal on the same side of the surface as `out_dir`.
+    normal: Vec3,
+    /// The relative index of refraction: that of the far side of the surface,
+    /// over that of the near side.
+    eta: f64,
+    /// Cosine of the angle between `out_dir` and the normal.
+    cos_out: f64,
+    /// Cosine of the angle of the transmitted light, or `None` under total
+    /// internal reflection.
+    cos_in: Option<f64>,
+}
+
+impl Crossing {
+    /// Works out how light crosses the surface with the given `normal`, which
+    /// faces from the inside, with index of refraction `eta_in`, to the
+    /// outside, with `eta_out`.
+    fn new(normal: Vec3, out_dir: Vec3, eta_in: f64, eta_out: f64) -> Self {
+        let cos_out = normal.dot(out_dir);
+        let (normal, eta, cos_out) = if cos_out < 0.0 {
+            (-normal, eta_out / eta_in, -cos_out)
+        } else {
+            (normal, eta_in / eta_out, cos_out)
+        };
+
+        let sin2_in = (1.0 - cos_out * cos_out) / (eta * eta);
+        let cos_in = if sin2_in < 1.0 {
+            Some((1.0 - sin2_in).sqrt())
+        } else {
+            None
+        };
+
+        Self {
+            normal,
+            eta,
+            cos_out,
+            cos_in,
+        }
+    }
+
+    /// The fraction of light reflected, rather than transmitted.
+    fn reflectance(&self) -> f64 {
+        match self.cos_in {
+            Some(cos_in) => {
+                let Self { eta, cos_out, .. } = *self;
+                let parallel = (eta * cos_out - cos_in) / (eta * cos_out + cos_in);
+                let perpendicular = (cos_out - eta * cos_in) / (cos_out + eta * cos_in);
+                0.5 * (parallel * parallel + perpendicular * perpendicular)
+            },
+            None => 1.0,
+        }
+    }
+}
+
+/// The Fresnel reflectance of a dielectric surface: the fraction of light in
+/// direction `out_dir` that comes from reflection rather than transmission.
+///
+/// The `normal` faces from the inside of the surface, with index of
+/// refraction `eta_in`, to the outside, with `eta_out`.
+pub(crate) fn fresnel(normal: Vec3, out_dir: Vec3, eta_in: f64, eta_out: f64) -> f64 {
+    Crossing::new(normal, out_dir, eta_in, eta_out).reflectance()
+}
+
+/// Refraction through a dielectric like glass or water, following the
+/// Fresnel equations.
+///
+/// The amount of light transmitted depends on the angle, with the rest being
+/// reflected (see [`FresnelReflector`](crate::brdf::FresnelReflector)). As a
+/// delta function [`BTDF::call`] is always black.
+#[derive(Debug, Clone)]
+pub struct FresnelTransmitter {
+    eta_in: f64,
+    eta_out: f64,
+}
+
+impl FresnelTransmitter {
+    /// Creates a new transmitter between a medium with index of refraction
+    /// `eta_in` on the inside of the surface, and `eta_out` on the outside.
+    pub fn new(eta_in: f64, eta_out: f64) -> Self {
+        Self { eta_in, eta_out }
+    }
+}
+
+impl BTDF for FresnelTransmitter {
+    fn call(&self, _hit: &Intersection, _in_dir: Vec3, _out_dir: Vec3) -> Colour {
+        Colour::black()
+    }
+
+    /// Always returns the refracted direction, with a PDF of `1`. The cosine
+    /// term is divided out of the result. Under total internal reflection
+    /// nothing is transmitted.
+    fn sample_f(&self, hit: &Intersection, out_dir: Vec3) -> (Colour, Vec3, f64) {
+        let crossing = Crossing::new(hit.normal, out_dir, self.eta_in, self.eta_out);
+        let Crossing {
+            normal,
+            eta,
+            cos_out,
+            ..
+        } = crossing;
+
+        match crossing.cos_in {
+            Some(cos_in) => {
+                let in_dir = -out_dir / eta - (cos_in - cos_out / eta) * normal;
+                let kt = 1.0 - crossing.reflectance();
+                let f = Colour::white() * kt / (eta * eta * cos_in);
+                (f, in_dir, 1.0)
+            },
+            None => (Colour::black(), -out_dir, 0.0),
+        }
+    }
+
+    fn rho(&self, _hit: &Intersection, _out_dir: Vec3) -> Colour {
+        Colour::black()
+    }
+
+    fn tir(&self, hit: &Intersection, out_dir: Vec3) -> bool {
+        let crossing = Crossing::new(hit.normal, out_dir, self.eta_in, self.eta_out);
+        crossing.cos_in.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NORMAL: Vec3 = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+
+    /// A direction at `degrees` from the normal, on the outside of the
+    /// surface, or on the inside if negative.
+    fn direction(degrees: f64) -> Vec3 {
+        let (sin, cos) = degrees.abs().to_radians().sin_cos();
+        Vec3::new(sin, 0.0, cos.copysign(degrees))
+    }
+
+    #[test]
+    fn normal_incidence() {
+        // ((1.5 - 1) / (1.5 + 1))² from either side
+        for &out_dir in &[NORMAL, -NORMAL] {
+            let kr = fresnel(NORMAL, out_dir, 1.5, 1.0);
+            assert!((kr - 0.04).abs() < 1e-12, "{:?}: {}", out_dir, kr);
+        }
+    }
+
+    #[test]
+    fn reflectance_rises_to_grazing() {
+        let mut previous = 0.0;
+        for degrees in (0..90).step_by(10) {
+            let kr = fresnel(NORMAL, direction(degrees as f64), 1.5, 1.0);
+            assert!(kr > previous - 1e-12 && kr < 1.0, "{}: {}", degrees, kr);
+            previous = kr;
+        }
+        assert!(fresnel(NORMAL, direction(89.99), 1.5, 1.0) > 0.99);
+    }
+
+    /// Light inside glass beyond the critical angle of about 41.8° can't
+    /// escape.
+    #[test]
+    fn total_internal_reflection() {
+        let critical = (1.0f64 / 1.5).asin().to_degrees();
+
+        let inside = Crossing::new(NORMAL, direction(-(critical - 1.0)), 1.5, 1.0);
+        assert!(inside.cos_in.is_some());
+        assert!(inside.reflectance() < 1.0);
+
+        for &degrees in &[critical + 0.1, 60.0, 89.0] {
+            let crossing = Crossing::new(NORMAL, direction(-degrees), 1.5, 1.0);
+            assert!(crossing.cos_in.is_none(), "{}", degrees);
+            assert_eq!(crossing.reflectance(), 1.0);
+        }
+
+        // never from the outside
+        let outside = Crossing::new(NORMAL, direction(89.0), 1.5, 1.0);
+        assert!(outside.cos_in.is_some());
+    }
+
+    /// The refracted angle follows Snell's law, on both sides.
+    #[test]
+    fn snells_law() {
+        for &(degrees, eta) in &[(30.0, 1.5), (-30.0, 1.0 / 1.5), (70.0, 1.5)] {
+            let crossing = Crossing::new(NORMAL, direction(degrees), 1.5, 1.0);
+            let sin_out = degrees.to_radians().sin().abs();
+            let sin_in = (1.0 - crossing.cos_in.unwrap().powi(2)).sqrt();
+            assert!((crossing.eta - eta).abs() < 1e-12);
+            assert!((sin_out - eta * sin_in).abs() < 1e-12, "{}", degrees);
+        }
+    }
+}
@@ -0,0 +1,463 @@
+//! Bounding volume hierarchies.
+//!
+//! Used to accelerate ray intersection tests against large numbers of
+//! objects: rather than testing every object in turn, objects are grouped
+//! into a tree of [bounding boxes](crate::geometry::BoundingBox), and whole
+//! subtrees are skipped whenever a ray misses their box.
+//!
+//! The tree is built top-down using the surface area heuristic (SAH), which
+//! estimates the cost of each potential split by the probability of a ray
+//! hitting either side of it.
+
+use crate::geometry::{BoundingBox, Geometry, Hit};
+use crate::utility::{Ray, Vec3};
+
+use std::f64;
+
+/// Number of buckets to use when estimating the best split for a node.
+const NUM_BUCKETS: usize = 12;
+
+/// Nodes with at most this many objects may be turned into leaves.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Estimated cost of traversing a node, relative to intersecting an object.
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// A node in the flattened tree.
+///
+/// Nodes are stored in depth-first order, so the first child of an interior
+/// node is always the node immediately following it.
+#[derive(Debug)]
+enum Node {
+    Interior {
+        bbox: BoundingBox,
+        /// Index of the second child.
+        second: usize,
+        /// Axis the children were split along.
+        axis: usize,
+    },
+    Leaf {
+        bbox: BoundingBox,
+        /// Index of the first object in the leaf.
+        start: usize,
+        /// Number of objects in the leaf.
+        count: usize,
+    },
+}
+
+/// Details of an object used while building the tree.
+#[derive(Debug, Clone, Copy)]
+struct BuildItem {
+    index: usize,
+    bbox: BoundingBox,
+    centroid: Vec3,
+}
+
+/// A bounding volume hierarchy over a collection of objects.
+///
+/// Objects without a [bounding box](Geometry::bounding_box), like planes, are
+/// kept to one side and always tested directly. Objects with an empty box can
+/// never be hit, so they're dropped.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::bvh::Bvh;
+/// use raytracer::geometry::{Geometry, Sphere};
+/// use raytracer::material::Matte;
+/// use raytracer::utility::{Colour, Ray, Vec3};
+///
+/// let objects = (0..100)
+///     .map(|i| -> Box<dyn Geometry> {
+///         Box::new(Sphere {
+///             centre: Vec3::new(3.0 * i as f64, 0.0, 0.0),
+///             radius: 1.0,
+///             material: Matte::new(0.25, 0.65, Colour::white()),
+///         })
+///     })
+///     .collect();
+/// let bvh = Bvh::new(objects);
+///
+/// let ray = Ray {
+///     origin: Vec3::new(30.0, 0.0, 10.0),
+///     direction: Vec3::new(0.0, 0.0, -1.0),
+/// };
+/// let hit = bvh.hit(&ray).unwrap();
+/// assert!((hit.t - 9.0).abs() < 1e-9);
+/// ```
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    bounded: Vec<Box<dyn Geometry>>,
+    unbounded: Vec<Box<dyn Geometry>>,
+}
+
+impl Bvh {
+    /// Builds a new hierarchy containing the given objects.
+    pub fn new(objects: Vec<Box<dyn Geometry>>) -> Self {
+        let mut bounded = Vec::new();
+        let mut unbounded = Vec::new();
+        let mut items = Vec::new();
+
+        for obj in objects {
+            match obj.bounding_box() {
+                Some(bbox) if bbox.is_empty() => {},
+                Some(bbox) => {
+                    items.push(BuildItem {
+                        index: bounded.len(),
+                        bbox,
+                        centroid: bbox.centroid(),
+                    });
+                    bounded.push(Some(obj));
+                },
+                None => unbounded.push(obj),
+            }
+        }
+
+        let mut nodes = Vec::new();
+        if !items.is_empty() {
+            build(&mut items, 0, &mut nodes);
+        }
+
+        // reorder the objects so that each leaf refers to a contiguous range
+        let bounded = items
+            .iter()
+            .map(|item| bounded[item.index].take().expect("object used twice"))
+            .collect();
+
+        Self {
+            nodes,
+            bounded,
+            unbounded,
+        }
+    }
+
+    /// The total number of objects in the hierarchy.
+    pub fn len(&self) -> usize {
+        self.bounded.len() + self.unbounded.len()
+    }
+
+    /// Whether there are no objects in the hierarchy.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over every object in the hierarchy, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Geometry> {
+        self.unbounded
+            .iter()
+            .chain(self.bounded.iter())
+            .map(|obj| obj.as_ref())
+    }
+
+    /// Finds the closest object hit by the given ray.
+    ///
+    /// Returns the details of the hit, as with [`Geometry::hit`].
+    pub fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let mut nearest = self
+            .unbounded
+            .iter()
+            .filter_map(|obj| obj.hit(ray))
+            .min_by(|a, b| a.t.partial_cmp(&b.t).expect("distance is NaN"));
+
+        let t_max = nearest.map_or(f64::INFINITY, |hit| hit.t);
+        self.traverse(ray, t_max, |objects, t_max| {
+            for obj in objects {
+                if let Some(hit) = obj.hit(ray) {
+                    if hit.t < *t_max {
+                        *t_max = hit.t;
+                        nearest = Some(hit);
+                    }
+                }
+            }
+            false
+        });
+
+        nearest
+    }
+
+    /// Determines whether the ray hits any object closer than `t_max`.
+    ///
+    /// This is cheaper than [`Bvh::hit`], since it can stop as soon as any
+    /// hit is found; useful for shadows.
+    pub fn hit_any(&self, ray: &Ray, t_max: f64) -> bool {
+        let hit_within = |obj: &dyn Geometry| match obj.hit(ray) {
+            Some(hit) => hit.t < t_max,
+            None => false,
+        };
+
+        self.unbounded.iter().any(|obj| hit_within(obj.as_ref()))
+            || self.traverse(ray, t_max, |objects, _| {
+                objects.iter().any(|obj| hit_within(obj.as_ref()))
+            })
+    }
+
+    /// Walks the tree, calling `visit` on the objects of every leaf whose
+    /// bounding box is hit by the ray before `t_max`.
+    ///
+    /// `visit` may shrink `t_max` as closer hits are found, and returns `true`
+    /// to stop the traversal early. Returns whether the traversal was stopped.
+    fn traverse<'a, F>(&'a self, ray: &Ray, mut t_max: f64, mut visit: F) -> bool
+    where
+        F: FnMut(&'a [Box<dyn Geometry>], &mut f64) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inv_dir = 1.0 / ray.direction;
+        let negative = [inv_dir.x < 0.0, inv_dir.y < 0.0, inv_dir.z < 0.0];
+
+        let mut stack = Vec::with_capacity(64);
+        let mut current = 0;
+        loop {
+            match self.nodes[current] {
+                Node::Interior { bbox, second, axis } => {
+                    if bbox.intersect(ray, inv_dir, t_max).is_some() {
+                        // visit the nearer child first, so that later hits
+                        // can be culled more aggressively
+                        if negative[axis] {
+                            stack.push(current + 1);
+                            current = second;
+                        } else {
+                            stack.push(second);
+                            current += 1;
+                        }
+                        continue;
+                    }
+                },
+                Node::Leaf { bbox, start, count } => {
+                    if bbox.intersect(ray, inv_dir, t_max).is_some()
+                        && visit(&self.bounded[start..start + count], &mut t_max)
+                    {
+                        return true;
+                    }
+                },
+            }
+
+            match stack.pop() {
+                Some(next) => current = next,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// Recursively builds the subtree for `items`, pushing its nodes in
+/// depth-first order and reordering `items` to match the leaves.
+///
+/// `offset` is the position of `items` within the full list of objects.
+/// Returns the index of the root of the subtree.
+fn build(items: &mut [BuildItem], offset: usize, nodes: &mut Vec<Node>) -> usize {
+    let index = nodes.len();
+    let bbox = items
+        .iter()
+        .fold(BoundingBox::empty(), |bbox, item| bbox.union(item.bbox));
+
+    let leaf = Node::Leaf {
+        bbox,
+        start: offset,
+        count: items.len(),
+    };
+    nodes.push(leaf);
+
+    if let Some((axis, mid)) = split(items, &bbox) {
+        let (left, right) = items.split_at_mut(mid);
+        build(left, offset, nodes);
+        let second = build(right, offset + mid, nodes);
+        nodes[index] = Node::Interior { bbox, second, axis };
+    }
+
+    index
+}
+
+/// Partitions `items` along the split minimising the surface area heuristic.
+///
+/// Returns the axis of the split and the number of items on the left side, or
+/// `None` if the items are better off left in a single leaf.
+fn split(items: &mut [BuildItem], bbox: &BoundingBox) -> Option<(usize, usize)> {
+    let count = items.len();
+    if count <= 1 {
+        return None;
+    }
+
+    let centroids = items
+        .iter()
+        .fold(BoundingBox::empty(), |b, item| b.include(item.centroid));
+
+    let bucket_of = |item: &BuildItem, axis: usize| {
+        let extent = centroids.max[axis] - centroids.min[axis];
+        let offset = (item.centroid[axis] - centroids.min[axis]) / extent;
+        ((offset * NUM_BUCKETS as f64) as usize).min(NUM_BUCKETS - 1)
+    };
+
+    // find the cheapest split over all axes: (cost, axis, last left bucket)
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        if centroids.max[axis] <= centroids.min[axis] {
+            continue;
+        }
+
+        let mut counts = [0; NUM_BUCKETS];
+        let mut bounds = [BoundingBox::empty(); NUM_BUCKETS];
+        for item in items.iter() {
+            let b = bucket_of(item, axis);
+            counts[b] += 1;
+            bounds[b] = bounds[b].union(item.bbox);
+        }
+
+        // sweep from the right to get the cost of everything after each split
+        let mut right_costs = [0.0; NUM_BUCKETS];
+        let mut right_bbox = BoundingBox::empty();
+        let mut right_count = 0;
+        for b in (1..NUM_BUCKETS).rev() {
+            right_bbox = right_bbox.union(bounds[b]);
+            right_count += counts[b];
+            right_costs[b - 1] = right_count as f64 * right_bbox.surface_area();
+        }
+
+        let mut left_bbox = BoundingBox::empty();
+        let mut left_count = 0;
+        for b in 0..NUM_BUCKETS - 1 {
+            left_bbox = left_bbox.union(bounds[b]);
+            left_count += counts[b];
+            if left_count == 0 || left_count == count {
+                continue;
+            }
+
+            let cost = left_count as f64 * left_bbox.surface_area() + right_costs[b];
+            let better = match best {
+                Some((best_cost, _, _)) => cost < best_cost,
+                None => true,
+            };
+            if better {
+                best = Some((cost, axis, b));
+            }
+        }
+    }
+
+    match best {
+        Some((cost, axis, bucket)) => {
+            let cost = TRAVERSAL_COST + cost / bbox.surface_area();
+            if count <= MAX_LEAF_SIZE && cost >= count as f64 {
+                return None;
+            }
+
+            // partition the items in place
+            let mut mid = 0;
+            for i in 0..count {
+                if bucket_of(&items[i], axis) <= bucket {
+                    items.swap(i, mid);
+                    mid += 1;
+                }
+            }
+            Some((axis, mid))
+        },
+        None if count <= MAX_LEAF_SIZE => None,
+        None => {
+            // every centroid is in the same place, so no split will help;
+            // just halve the items to keep the leaves small
+            Some((bbox.longest_axis(), count / 2))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Cuboid, Plane, Sphere, Triangle};
+    use crate::material::Matte;
+    use crate::sampler;
+    use crate::utility::Colour;
+
+    use rand::Rng;
+
+    /// The hierarchy must find exactly the same hits as testing every object
+    /// in turn, including the unbounded planes kept outside the tree.
+    #[test]
+    fn matches_linear_scan() {
+        sampler::seed(1);
+        let mut rng = sampler::rng();
+        let mut point = |scale: f64| {
+            Vec3::new(
+                rng.gen_range(-scale, scale),
+                rng.gen_range(-scale, scale),
+                rng.gen_range(-scale, scale),
+            )
+        };
+        // clones share their samples, which are slow to generate
+        let matte = Matte::new(0.25, 0.65, Colour::white());
+        let material = || matte.clone();
+
+        let mut objects: Vec<Box<dyn Geometry>> = Vec::new();
+        for i in 0..200 {
+            let centre = point(20.0);
+            if i % 2 == 0 {
+                objects.push(Box::new(Sphere {
+                    centre,
+                    radius: 0.5 + (i % 7) as f64 * 0.2,
+                    material: material(),
+                }));
+            } else {
+                let size = Vec3::new(0.5, 1.0, 1.5);
+                objects.push(Box::new(Cuboid::new(
+                    centre - size,
+                    centre + size,
+                    material(),
+                )));
+            }
+        }
+        for normal in &[Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 1.0)] {
+            objects.push(Box::new(Plane {
+                point: Vec3::new(0.0, -25.0, 0.0),
+                normal: normal.normalise(),
+                material: material(),
+            }));
+        }
+
+        let rays: Vec<_> = (0..2000)
+            .map(|_| Ray {
+                origin: point(30.0),
+                direction: point(1.0).normalise(),
+            })
+            .collect();
+        let expected: Vec<_> = rays
+            .iter()
+            .map(|ray| {
+                objects
+                    .iter()
+                    .filter_map(|obj| obj.hit(ray))
+                    .map(|hit| hit.t)
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect();
+
+        let bvh = Bvh::new(objects);
+        assert_eq!(bvh.len(), 202);
+        for (ray, &t) in rays.iter().zip(&expected) {
+            let hit = bvh.hit(ray).map_or(f64::INFINITY, |hit| hit.t);
+            assert_eq!(hit, t, "closest hit for {:?}", ray);
+            assert_eq!(bvh.hit_any(ray, f64::INFINITY), t.is_finite());
+            assert!(!bvh.hit_any(ray, t * 0.99), "shadow hit for {:?}", ray);
+        }
+    }
+
+    /// Rays parallel to an axis which run exactly along the side of a box
+    /// still enter it, to hit what's on its surface.
+    #[test]
+    fn ray_along_box_side() {
+        let triangle = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Matte::new(0.25, 0.65, Colour::white()),
+        );
+        let bvh = Bvh::new(vec![Box::new(triangle)]);
+        let ray = Ray {
+            origin: Vec3::new(1.0, 0.5, 2.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        let hit = bvh.hit(&ray).expect("ray along the side missed");
+        assert_eq!(hit.t, 2.0);
+    }
+}
@@ -2,28 +2,112 @@
 
 use std::f64::consts;
 use std::fmt::Debug;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc;
+use std::thread;
 
-use crate::sampler::Generator;
+use crate::film::Film;
+use crate::sampler::{self, Generator, Samples};
 use crate::tracer::Tracer;
 use crate::utility::{Colour, Ray, Vec2, Vec3};
 use crate::world::{ViewPlane, World};
 
-use image::{Rgb, RgbImage};
 use indicatif::{ProgressBar, ProgressStyle};
+use rand::Rng;
 
 /// Renders scenes.
 ///
 /// Different cameras will use different projections and/or techniques to do
 /// rendering. Look at the documentation for each individual camera to determine
 /// what they do.
-pub trait Camera: Debug {
+///
+/// Most cameras only need to say which ray passes through each point on the
+/// view plane, by implementing [`RayGenerator`], and are rendered by
+/// [`render`].
+pub trait Camera: Debug + Send + Sync {
     /// Renders the scene for the given `world`.
     ///
     /// You may pass different tracers to render in different ways.
     /// See the [`tracer`][crate::tracer] module for more details.
     ///
-    /// Returns the rendered image buffer.
-    fn render_scene<T: Tracer>(&self, world: &World, tracer: T) -> RgbImage;
+    /// Returns the rendered [film](crate::film), with the unclamped colour of
+    /// each pixel.
+    fn render_scene(
+        &self,
+        world: &World,
+        tracer: &dyn Tracer,
+        options: &RenderOptions,
+    ) -> Film;
+}
+
+/// Generates the rays cast from a camera into the world.
+///
+/// This is the part of a camera which differs between projections. Every ray
+/// generator is a [`Camera`], rendered by the shared driver in [`render`].
+pub trait RayGenerator: Debug + Send + Sync {
+    /// Generates the ray through a point on the view plane.
+    ///
+    /// - `pixel` is the point on the view plane, measured in pixels from its
+    ///   centre, with `y` pointing up
+    /// - `lens` is a point on the unit disc, for cameras with a lens of some
+    ///   size
+    /// - `time` is when the ray is cast, from `0` at the start of the exposure
+    ///   to `1` at the end
+    ///
+    /// Returns `None` if no ray passes through the point, which leaves it
+    /// black.
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        lens: Vec2,
+        time: f64,
+    ) -> Option<Ray>;
+
+    /// Generates the points on the lens passed to
+    /// [`generate_ray`](RayGenerator::generate_ray), one for each pixel
+    /// sample.
+    ///
+    /// Defaults to using the view plane's sampler.
+    fn gen_lens_samples(&self, view: &ViewPlane) -> Samples<Vec2> {
+        view.sampler.gen_disc_samples()
+    }
+}
+
+impl<G: RayGenerator + ?Sized> Camera for G {
+    fn render_scene(
+        &self,
+        world: &World,
+        tracer: &dyn Tracer,
+        options: &RenderOptions,
+    ) -> Film {
+        render(self, world, tracer, options)
+    }
+}
+
+/// Options controlling the rendering process itself.
+///
+/// These don't affect the rendered image: given the same
+/// [seed](crate::sampler::seed), the result is identical no matter how many
+/// threads are used.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Whether to display a progress bar while rendering.
+    pub progress: bool,
+    /// The number of worker threads to render with.
+    ///
+    /// Defaults to the number of CPUs available.
+    pub threads: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            progress: true,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
 }
 
 /// A user-specified camera location.
@@ -47,36 +131,161 @@ fn compute_basis_vectors(Location { eye, centre, up }: &Location) -> (Vec3, Vec3
     (u, v, w)
 }
 
+/// Width and height of the square tiles the image is split into.
+const TILE_SIZE: u32 = 32;
+
 /// Call the given function for every pixel in the view plane.
-fn loop_through_viewplane<F>(view: &ViewPlane, mut colour_fn: F) -> RgbImage
+///
+/// The image is split into tiles, which are shared out between worker
+/// threads. Each tile gets its own state from `make_state`, which is passed
+/// to `colour_fn` for every pixel in the tile. This is where any [`Samples`]
+/// should be [shuffled](Samples::shuffled), since they must be mutable;
+/// generating them anew for every tile would be wasteful.
+///
+/// Before each tile is started the sampling [RNG](crate::sampler::rng) of its
+/// thread is reseeded, based on the tile's position and a seed drawn from the
+/// calling thread. The result therefore doesn't depend on which thread renders
+/// which tile.
+fn loop_through_viewplane<S, F, C>(
+    view: &ViewPlane,
+    options: &RenderOptions,
+    make_state: S,
+    colour_fn: C,
+) -> Film
 where
-    F: FnMut(Vec2) -> Colour,
+    S: Fn() -> F + Sync,
+    C: Fn(&mut F, Vec2) -> Colour + Sync,
 {
-    let mut img = RgbImage::new(view.hres, view.vres);
+    let mut film = Film::new(view.hres, view.vres);
 
     let width = f64::from(view.hres - 1);
     let height = f64::from(view.vres - 1);
 
+    let tiles_x = view.hres.div_ceil(TILE_SIZE);
+    let tiles_y = view.vres.div_ceil(TILE_SIZE);
+    let num_tiles = tiles_x * tiles_y;
+
     let style = ProgressStyle::default_bar()
         .template("[{elapsed_precise}] {bar:50} {percent}% (ETA: {eta})");
-    let bar = ProgressBar::new(view.hres as u64).with_style(style);
-
-    for col in 0..view.hres {
-        bar.inc(1);
-        for row in 0..view.vres {
-            let pixel = Vec2 {
-                x: (col as f64) - width * 0.5,
-                y: height * 0.5 - (row as f64),
-            };
-
-            let colour = colour_fn(pixel);
-            img.put_pixel(col, row, Rgb::from(colour));
+    let bar = if options.progress {
+        ProgressBar::new(u64::from(num_tiles)).with_style(style)
+    } else {
+        ProgressBar::hidden()
+    };
+
+    let base_seed: u64 = sampler::rng().gen();
+    let next_tile = AtomicU32::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    let render_tile = |tile: u32| {
+        sampler::seed(base_seed.wrapping_add(u64::from(tile)));
+        let mut state = make_state();
+
+        let x0 = (tile % tiles_x) * TILE_SIZE;
+        let y0 = (tile / tiles_x) * TILE_SIZE;
+        let x1 = (x0 + TILE_SIZE).min(view.hres);
+        let y1 = (y0 + TILE_SIZE).min(view.vres);
+
+        let mut pixels = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for col in x0..x1 {
+            for row in y0..y1 {
+                let pixel = Vec2 {
+                    x: (col as f64) - width * 0.5,
+                    y: height * 0.5 - (row as f64),
+                };
+
+                let colour = colour_fn(&mut state, pixel);
+                pixels.push((col, row, colour));
+            }
         }
-    }
+        pixels
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..options.threads.clamp(1, num_tiles as usize) {
+            let sender = sender.clone();
+            let next_tile = &next_tile;
+            let render_tile = &render_tile;
+            scope.spawn(move || loop {
+                let tile = next_tile.fetch_add(1, Ordering::Relaxed);
+                if tile >= num_tiles {
+                    break;
+                }
+                if sender.send(render_tile(tile)).is_err() {
+                    break;
+                }
+            });
+        }
+        // let the loop below finish once every worker is done
+        drop(sender);
+
+        for pixels in receiver {
+            for (col, row, colour) in pixels {
+                film.add_sample(col, row, colour, 1.0);
+            }
+            bar.inc(1);
+        }
+    });
 
     bar.finish_and_clear();
 
-    return img;
+    film
+}
+
+/// Renders the world with the rays from a [`RayGenerator`].
+///
+/// Every pixel is sampled using the view plane's sampler, with a ray traced
+/// for each sample, and the pixel's colour is their average. Samples without a
+/// ray count as black. Each ray is traced [as one sample of its
+/// pixel](sampler::with_pixel_sample), so that the lights and materials it
+/// meets spread their samples over the pixel's rays.
+///
+/// # Panics
+///
+/// Panics if the camera's [lens samples](RayGenerator::gen_lens_samples) don't
+/// have the same number of samples as the view plane's sampler.
+pub fn render<G: RayGenerator + ?Sized>(
+    camera: &G,
+    world: &World,
+    tracer: &dyn Tracer,
+    options: &RenderOptions,
+) -> Film {
+    let view = &world.view;
+    let num_samples = view.sampler.num_samples();
+
+    let pixel_samples = view.sampler.gen_square_samples();
+    let lens_samples = camera.gen_lens_samples(view);
+    assert!(lens_samples.num_samples() == num_samples);
+
+    let make_samples = || (pixel_samples.shuffled(), lens_samples.shuffled());
+    loop_through_viewplane(view, options, make_samples, |samples, pixel| {
+        let (pixel_samples, lens_samples) = samples;
+        let mut rng = sampler::rng();
+        let key = rng.gen();
+        pixel_samples
+            .get_next()
+            .iter()
+            .zip(lens_samples.get_next().iter())
+            .enumerate()
+            .fold(
+                Colour::black(),
+                |accum, (index, (&sample, &lens))| match camera.generate_ray(
+                    view,
+                    pixel + sample,
+                    lens,
+                    rng.gen(),
+                ) {
+                    Some(ray) => {
+                        let colour = sampler::with_pixel_sample(key, index, || {
+                            tracer.trace_ray(world, ray, 0)
+                        });
+                        accum + colour
+                    },
+                    None => accum,
+                },
+            )
+            / num_samples as f64
+    })
 }
 
 /// A virtual pinhole camera.
@@ -86,12 +295,12 @@ where
 /// a decent default for now.
 #[derive(Debug)]
 pub struct Pinhole {
-    /// Ratio of exposure.
-    exposure: f64,
     /// Distance to the view plane.
     view_len: f64,
     /// Zoom factor.
     zoom: f64,
+    /// Horizontal offset of the view plane, for off-axis [`Stereo`] views.
+    shift: f64,
     /// The position of the camera.
     eye: Vec3,
     /// Orthonormal basis vectors for the camera.
@@ -103,42 +312,84 @@ impl Pinhole {
         let basis = compute_basis_vectors(&location);
         Self {
             eye: location.eye,
-            exposure: 1.0,
             basis,
             view_len,
             zoom,
+            shift: 0.0,
         }
     }
 
     fn ray_direction(&self, p: Vec2) -> Vec3 {
         let (u, v, w) = self.basis;
-        (u * p.x + v * p.y - w * self.view_len).normalise()
+        (u * (p.x + self.shift) + v * p.y - w * self.view_len).normalise()
     }
 }
 
-impl Camera for Pinhole {
-    fn render_scene<T: Tracer>(&self, world: &World, tracer: T) -> RgbImage {
-        let mut samples = world.view.sampler.gen_square_samples();
-        let num_samples = samples.num_samples() as f64;
+impl RayGenerator for Pinhole {
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        _lens: Vec2,
+        _time: f64,
+    ) -> Option<Ray> {
+        let point = pixel * view.s / self.zoom;
+        Some(Ray {
+            origin: self.eye,
+            direction: self.ray_direction(point),
+        })
+    }
+}
 
-        let origin = self.eye;
-        let scale = world.view.s / self.zoom;
+/// A parallel projection, with no perspective.
+///
+/// Every ray travels in the same direction, from a point on a plane through
+/// the eye. Objects stay the same size however far away they are, and parallel
+/// lines stay parallel, as in a technical drawing. Anything behind the eye is
+/// not seen.
+#[derive(Debug)]
+pub struct Orthographic {
+    /// Width of the view in world units.
+    width: f64,
+    /// The position of the camera.
+    eye: Vec3,
+    /// Orthonormal basis vectors for the camera.
+    basis: (Vec3, Vec3, Vec3),
+}
 
-        loop_through_viewplane(&world.view, |pixel| {
-            samples
-                .get_next()
-                .iter()
-                .fold(Colour::black(), |accum, &sample| {
-                    let point = (pixel + sample) * scale;
-                    let direction = self.ray_direction(point);
+impl Orthographic {
+    /// Create a new orthographic camera.
+    ///
+    /// The `width` is how much of the world is seen across the image, in world
+    /// units. The height is chosen to keep the image's aspect ratio.
+    pub fn new(location: Location, width: f64) -> Self {
+        let basis = compute_basis_vectors(&location);
+        Self {
+            width,
+            eye: location.eye,
+            basis,
+        }
+    }
 
-                    let ray = Ray { origin, direction };
-                    let colour = tracer.trace_ray(world, ray);
+    fn ray_origin(&self, p: Vec2) -> Vec3 {
+        let (u, v, _) = self.basis;
+        self.eye + u * p.x + v * p.y
+    }
+}
 
-                    accum + colour
-                })
-                * self.exposure
-                / num_samples
+impl RayGenerator for Orthographic {
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        _lens: Vec2,
+        _time: f64,
+    ) -> Option<Ray> {
+        let (_, _, w) = self.basis;
+        let point = pixel * self.width / f64::from(view.hres);
+        Some(Ray {
+            origin: self.ray_origin(point),
+            direction: -w,
         })
     }
 }
@@ -152,8 +403,6 @@ impl Camera for Pinhole {
 /// in-focus.
 #[derive(Debug)]
 pub struct ThinLens<G: Generator> {
-    /// Ratio of exposure.
-    exposure: f64,
     /// Size of the lens
     lens_radius: f64,
     /// View plane distance
@@ -181,7 +430,6 @@ impl<G: Generator> ThinLens<G> {
     ) -> Self {
         let basis = compute_basis_vectors(&location);
         Self {
-            exposure: 1.0,
             lens_radius,
             view_len,
             focal_len,
@@ -205,37 +453,27 @@ impl<G: Generator> ThinLens<G> {
     }
 }
 
-impl<G: Generator> Camera for ThinLens<G> {
-    fn render_scene<T: Tracer>(&self, world: &World, tracer: T) -> RgbImage {
-        let mut pixel_samples = world.view.sampler.gen_square_samples();
-        let mut disc_samples = self.sampler.gen_disc_samples();
-
-        assert!(pixel_samples.num_samples() == disc_samples.num_samples());
-        let num_samples = pixel_samples.num_samples() as f64;
-
-        let scale = world.view.s / self.zoom;
-
-        loop_through_viewplane(&world.view, |pixel| {
-            pixel_samples
-                .get_next()
-                .iter()
-                .zip(disc_samples.get_next().iter())
-                .fold(Colour::black(), |accum, (&sample, &disc_point)| {
-                    let pixel_point = (pixel + sample) * scale;
-                    let lens_point = disc_point * self.lens_radius;
-
-                    let ray = Ray {
-                        origin: self.ray_origin(lens_point),
-                        direction: self.ray_direction(pixel_point, lens_point),
-                    };
-                    let colour = tracer.trace_ray(world, ray);
-
-                    accum + colour
-                })
-                * self.exposure
-                / num_samples
+impl<G: Generator> RayGenerator for ThinLens<G> {
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        lens: Vec2,
+        _time: f64,
+    ) -> Option<Ray> {
+        let pixel_point = pixel * view.s / self.zoom;
+        let lens_point = lens * self.lens_radius;
+        Some(Ray {
+            origin: self.ray_origin(lens_point),
+            direction: self.ray_direction(pixel_point, lens_point),
         })
     }
+
+    /// Lens samples come from the camera's own sampler, which must have the
+    /// same number of samples as the view plane's.
+    fn gen_lens_samples(&self, _view: &ViewPlane) -> Samples<Vec2> {
+        self.sampler.gen_disc_samples()
+    }
 }
 
 /// Fisheye camera.
@@ -246,8 +484,6 @@ impl<G: Generator> Camera for ThinLens<G> {
 /// as black.
 #[derive(Debug)]
 pub struct Fisheye {
-    /// Ratio of exposure.
-    exposure: f64,
     /// Derives the field-of-view angle.
     psi_max: f64,
     /// The position of the camera.
@@ -266,7 +502,6 @@ impl Fisheye {
         let psi_max = view_angle.to_radians() / 2.0;
         let basis = compute_basis_vectors(&location);
         Self {
-            exposure: 1.0,
             psi_max,
             eye: location.eye,
             basis,
@@ -297,30 +532,18 @@ impl Fisheye {
     }
 }
 
-impl Camera for Fisheye {
-    fn render_scene<T: Tracer>(&self, world: &World, tracer: T) -> RgbImage {
-        let mut samples = world.view.sampler.gen_square_samples();
-        let num_samples = samples.num_samples() as f64;
-
-        let origin = self.eye;
-        let scale = world.view.s;
-
-        loop_through_viewplane(&world.view, |pixel| {
-            samples
-                .get_next()
-                .iter()
-                .fold(Colour::black(), |accum, &sample| {
-                    let point = (pixel + sample) * scale;
-                    if let Some(direction) = self.ray_direction(point, &world.view) {
-                        let ray = Ray { origin, direction };
-                        let colour = tracer.trace_ray(&world, ray);
-                        accum + colour
-                    } else {
-                        accum
-                    }
-                })
-                * self.exposure
-                / num_samples
+impl RayGenerator for Fisheye {
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        _lens: Vec2,
+        _time: f64,
+    ) -> Option<Ray> {
+        let direction = self.ray_direction(pixel * view.s, view)?;
+        Some(Ray {
+            origin: self.eye,
+            direction,
         })
     }
 }
@@ -332,8 +555,6 @@ impl Camera for Fisheye {
 /// work even outside of circles.
 #[derive(Debug)]
 pub struct Spherical {
-    /// Ratio of exposure.
-    exposure: f64,
     /// Derives the field-of-view angle on the `(u, w)` plane, i.e.
     /// horizontally.
     max_azimuth: f64,
@@ -356,7 +577,6 @@ impl Spherical {
         let max_polar = polar.to_radians();
         let basis = compute_basis_vectors(&location);
         Self {
-            exposure: 1.0,
             max_azimuth,
             max_polar,
             eye: location.eye,
@@ -385,27 +605,418 @@ impl Spherical {
     }
 }
 
-impl Camera for Spherical {
-    fn render_scene<T: Tracer>(&self, world: &World, tracer: T) -> RgbImage {
-        let mut samples = world.view.sampler.gen_square_samples();
-        let num_samples = samples.num_samples() as f64;
-
-        let origin = self.eye;
-        let scale = world.view.s;
-
-        loop_through_viewplane(&world.view, |pixel| {
-            samples
-                .get_next()
-                .iter()
-                .fold(Colour::black(), |accum, &sample| {
-                    let point = (pixel + sample) * scale;
-                    let direction = self.ray_direction(point, &world.view);
-                    let ray = Ray { origin, direction };
-                    let colour = tracer.trace_ray(&world, ray);
-                    accum + colour
-                })
-                * self.exposure
-                / num_samples
+impl RayGenerator for Spherical {
+    fn generate_ray(
+        &self,
+        view: &ViewPlane,
+        pixel: Vec2,
+        _lens: Vec2,
+        _time: f64,
+    ) -> Option<Ray> {
+        Some(Ray {
+            origin: self.eye,
+            direction: self.ray_direction(pixel * view.s, view),
         })
     }
 }
+
+/// How the eyes of a [`Stereo`] camera are aimed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convergence {
+    /// Both eyes look straight ahead, so everything appears in front of the
+    /// screen.
+    Parallel,
+    /// Both eyes turn inwards to look at the centre. This is simple, but
+    /// objects towards the sides of the image are misaligned vertically.
+    ToeIn,
+    /// Both eyes look straight ahead, with their views shifted sideways so
+    /// that they line up at the distance of the centre, which appears at the
+    /// screen. This is usually the most comfortable to view.
+    OffAxis,
+}
+
+/// How the views of a [`Stereo`] camera are combined into a single image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// The left view beside the right view, in an image twice as wide.
+    SideBySide,
+    /// The left view above the right view, in an image twice as tall.
+    OverUnder,
+    /// The red channel of the left view and the green and blue channels of
+    /// the right view, for red/cyan glasses.
+    Anaglyph,
+}
+
+impl StereoLayout {
+    /// Combines the views from each eye, which must be the same size.
+    fn combine(self, left: &Film, right: &Film) -> Film {
+        let (width, height) = (left.width(), left.height());
+        let mut film = match self {
+            StereoLayout::SideBySide => Film::new(2 * width, height),
+            StereoLayout::OverUnder => Film::new(width, 2 * height),
+            StereoLayout::Anaglyph => Film::new(width, height),
+        };
+
+        for ((x, y, l), (_, _, r)) in left.pixels().zip(right.pixels()) {
+            match self {
+                StereoLayout::SideBySide => {
+                    film.add_sample(x, y, l, 1.0);
+                    film.add_sample(x + width, y, r, 1.0);
+                },
+                StereoLayout::OverUnder => {
+                    film.add_sample(x, y, l, 1.0);
+                    film.add_sample(x, y + height, r, 1.0);
+                },
+                StereoLayout::Anaglyph => {
+                    film.add_sample(x, y, Colour::new(l.r, r.g, r.b), 1.0);
+                },
+            }
+        }
+        film
+    }
+}
+
+/// A stereoscopic camera, for 3D displays.
+///
+/// This renders the world with a camera for each eye, and combines their
+/// views into one image with the given [layout](StereoLayout).
+///
+/// # Example
+///
+/// ```
+/// use raytracer::camera::{Convergence, Location, Stereo, StereoLayout};
+/// use raytracer::utility::Vec3;
+///
+/// let location = Location {
+///     eye: Vec3::new(0.0, 5.0, 50.0),
+///     centre: Vec3::new(0.0, 5.0, 0.0),
+///     up: Vec3::new(0.0, 1.0, 0.0),
+/// };
+/// // eyes 2 units apart, converging on the centre
+/// let camera = Stereo::pinhole(
+///     location,
+///     40.0,
+///     1.0,
+///     2.0,
+///     Convergence::OffAxis,
+///     StereoLayout::Anaglyph,
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Stereo<C: RayGenerator> {
+    /// The camera for the left eye.
+    left: C,
+    /// The camera for the right eye.
+    right: C,
+    /// How the two views are combined.
+    layout: StereoLayout,
+}
+
+impl<C: RayGenerator> Stereo<C> {
+    /// Create a stereo camera from a camera for each eye.
+    ///
+    /// The cameras should render images of the same size, so generally they
+    /// differ only in their location.
+    pub fn new(left: C, right: C, layout: StereoLayout) -> Self {
+        Self {
+            left,
+            right,
+            layout,
+        }
+    }
+}
+
+impl Stereo<Pinhole> {
+    /// Create a stereo pair of [`Pinhole`] cameras.
+    ///
+    /// The eyes are `separation` apart, either side of the location's eye
+    /// along the `u` basis vector. Unless the eyes are parallel, they converge
+    /// on the location's centre.
+    pub fn pinhole(
+        location: Location,
+        view_len: f64,
+        zoom: f64,
+        separation: f64,
+        convergence: Convergence,
+        layout: StereoLayout,
+    ) -> Self {
+        let (left_location, right_location) =
+            eye_locations(&location, separation, convergence);
+        let mut left = Pinhole::new(left_location, view_len, zoom);
+        let mut right = Pinhole::new(right_location, view_len, zoom);
+
+        if convergence == Convergence::OffAxis {
+            // move each view so the centre is in the middle of both images
+            let distance = (location.centre - location.eye).mag();
+            let shift = 0.5 * separation * view_len / distance;
+            left.shift = shift;
+            right.shift = -shift;
+        }
+        Self::new(left, right, layout)
+    }
+}
+
+/// The locations of the left and right eyes of a stereo camera, `separation`
+/// apart.
+fn eye_locations(
+    location: &Location,
+    separation: f64,
+    convergence: Convergence,
+) -> (Location, Location) {
+    let (u, _, _) = compute_basis_vectors(location);
+    let eye = |offset: Vec3| Location {
+        eye: location.eye + offset,
+        centre: match convergence {
+            Convergence::ToeIn => location.centre,
+            Convergence::Parallel | Convergence::OffAxis => location.centre + offset,
+        },
+        up: location.up,
+    };
+    let offset = 0.5 * separation * u;
+    (eye(-offset), eye(offset))
+}
+
+impl<C: RayGenerator> Camera for Stereo<C> {
+    fn render_scene(
+        &self,
+        world: &World,
+        tracer: &dyn Tracer,
+        options: &RenderOptions,
+    ) -> Film {
+        let left = render(&self.left, world, tracer, options);
+        let right = render(&self.right, world, tracer, options);
+        self.layout.combine(&left, &right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::geometry::{Plane, Sphere};
+    use crate::light::{Ambient, AreaLight, Shape};
+    use crate::material::Matte;
+    use crate::sampler::Jittered;
+    use crate::tracer::PathTracer;
+
+    /// Every tile is seeded by its position, so the image mustn't depend on
+    /// how many threads share out the tiles.
+    #[test]
+    fn threads_render_identically() {
+        let sphere = Sphere {
+            centre: Vec3::new(0.0, 1.0, 0.0),
+            radius: 1.0,
+            material: Matte::new(0.0, 0.8, Colour::new(0.9, 0.5, 0.3)),
+        };
+        let floor = Plane {
+            point: Vec3::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            material: Matte::new(0.0, 0.7, Colour::white()),
+        };
+        let light = AreaLight::new(
+            Shape::Disc {
+                centre: Vec3::new(0.0, 4.0, 1.0),
+                normal: Vec3::new(0.0, -1.0, 0.0),
+                radius: 1.0,
+            },
+            5.0,
+            Colour::white(),
+            &Jittered::new(4),
+        );
+        let world = World {
+            background: Colour::new(0.1, 0.1, 0.2),
+            environment: None,
+            view: ViewPlane::new(70, 40, 0.02, Jittered::new(4)),
+            objects: Bvh::new(vec![Box::new(sphere), Box::new(floor)]),
+            ambient: Box::new(Ambient::new(0.0)),
+            lights: vec![Box::new(light)],
+        };
+        let location = Location {
+            eye: Vec3::new(0.0, 1.5, 6.0),
+            centre: Vec3::new(0.0, 1.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+        };
+        let camera = Pinhole::new(location, 1.0, 1.0);
+        let tracer = PathTracer::new(5, 3);
+
+        let render_with = |threads| {
+            sampler::seed(5);
+            let options = RenderOptions {
+                progress: false,
+                threads,
+            };
+            render(&camera, &world, &tracer, &options)
+        };
+        let single = render_with(1);
+        let multi = render_with(4);
+
+        assert!(single
+            .pixels()
+            .any(|(_, _, colour)| colour != Colour::black()));
+        assert!(single.pixels().eq(multi.pixels()));
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).mag() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// A camera on the z axis, looking back at the origin.
+    fn on_z_axis() -> Location {
+        Location {
+            eye: Vec3::new(0.0, 0.0, 10.0),
+            centre: Vec3::new(0.0, 0.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let view = ViewPlane::new(100, 50, 1.0, Jittered::new(1));
+        let camera = Orthographic::new(on_z_axis(), 4.0);
+        let ray = |x, y| {
+            camera
+                .generate_ray(&view, Vec2::new(x, y), Vec2::new(0.0, 0.0), 0.0)
+                .unwrap()
+        };
+
+        let centre = ray(0.0, 0.0);
+        assert_close(centre.origin, Vec3::new(0.0, 0.0, 10.0));
+        assert_close(centre.direction, Vec3::new(0.0, 0.0, -1.0));
+
+        // half the image's width is half the view's width
+        let right = ray(50.0, 0.0);
+        assert_close(right.origin, Vec3::new(2.0, 0.0, 10.0));
+        assert_close(right.direction, Vec3::new(0.0, 0.0, -1.0));
+
+        // pixels are square, so the height follows the aspect ratio
+        let corner = ray(-50.0, 25.0);
+        assert_close(corner.origin, Vec3::new(-2.0, 1.0, 10.0));
+        assert_close(corner.direction, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn orthographic_follows_the_location() {
+        let view = ViewPlane::new(100, 100, 1.0, Jittered::new(1));
+        let location = Location {
+            eye: Vec3::new(10.0, 3.0, 0.0),
+            centre: Vec3::new(0.0, 3.0, 0.0),
+            up: Vec3::new(0.0, 1.0, 0.0),
+        };
+        let camera = Orthographic::new(location, 10.0);
+        let ray = camera
+            .generate_ray(&view, Vec2::new(50.0, 50.0), Vec2::new(0.0, 0.0), 0.0)
+            .unwrap();
+
+        // looking down -x, the right of the image is -z
+        assert_close(ray.origin, Vec3::new(10.0, 8.0, -5.0));
+        assert_close(ray.direction, Vec3::new(-1.0, 0.0, 0.0));
+    }
+
+    /// The rays through the middle of the left and right views of a stereo
+    /// camera on the z axis, with eyes 2 units apart.
+    fn stereo_centre_rays(convergence: Convergence) -> (Ray, Ray) {
+        let view = ViewPlane::new(100, 100, 1.0, Jittered::new(1));
+        let camera = Stereo::pinhole(
+            on_z_axis(),
+            100.0,
+            1.0,
+            2.0,
+            convergence,
+            StereoLayout::SideBySide,
+        );
+        let ray = |eye: &Pinhole| {
+            eye.generate_ray(&view, Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0), 0.0)
+                .unwrap()
+        };
+        (ray(&camera.left), ray(&camera.right))
+    }
+
+    #[test]
+    fn stereo_eyes_are_separated() {
+        for &convergence in &[
+            Convergence::Parallel,
+            Convergence::ToeIn,
+            Convergence::OffAxis,
+        ] {
+            let (left, right) = stereo_centre_rays(convergence);
+            assert_close(left.origin, Vec3::new(-1.0, 0.0, 10.0));
+            assert_close(right.origin, Vec3::new(1.0, 0.0, 10.0));
+        }
+    }
+
+    #[test]
+    fn stereo_parallel_eyes_look_straight_ahead() {
+        let (left, right) = stereo_centre_rays(Convergence::Parallel);
+        assert_close(left.direction, Vec3::new(0.0, 0.0, -1.0));
+        assert_close(right.direction, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn stereo_converging_eyes_meet_at_the_centre() {
+        for &convergence in &[Convergence::ToeIn, Convergence::OffAxis] {
+            let (left, right) = stereo_centre_rays(convergence);
+            for ray in &[left, right] {
+                let to_centre = (Vec3::new(0.0, 0.0, 0.0) - ray.origin).normalise();
+                assert_close(ray.direction, to_centre);
+            }
+        }
+    }
+
+    #[test]
+    fn stereo_only_toe_in_turns_the_eyes() {
+        let basis = |convergence| {
+            let camera = Stereo::pinhole(
+                on_z_axis(),
+                100.0,
+                1.0,
+                2.0,
+                convergence,
+                StereoLayout::SideBySide,
+            );
+            (camera.left.basis, camera.right.basis)
+        };
+        // off-axis eyes converge by shifting their views, not turning
+        let (left, right) = basis(Convergence::OffAxis);
+        assert_eq!(left, right);
+        assert_close(left.2, Vec3::new(0.0, 0.0, 1.0));
+
+        let (left, right) = basis(Convergence::ToeIn);
+        assert!(left.2.x < 0.0 && right.2.x > 0.0);
+    }
+
+    /// Two 2x1 films, white on the left and grey on the right.
+    fn eye_films() -> (Film, Film) {
+        let mut left = Film::new(2, 1);
+        let mut right = Film::new(2, 1);
+        for x in 0..2 {
+            left.add_sample(x, 0, Colour::white(), 1.0);
+            right.add_sample(x, 0, Colour::new(0.5, 0.5, 0.5), 1.0);
+        }
+        (left, right)
+    }
+
+    #[test]
+    fn stereo_layouts() {
+        let (left, right) = eye_films();
+        let grey = Colour::new(0.5, 0.5, 0.5);
+
+        let film = StereoLayout::SideBySide.combine(&left, &right);
+        assert_eq!((film.width(), film.height()), (4, 1));
+        assert_eq!(film.pixel(1, 0), Colour::white());
+        assert_eq!(film.pixel(2, 0), grey);
+
+        let film = StereoLayout::OverUnder.combine(&left, &right);
+        assert_eq!((film.width(), film.height()), (2, 2));
+        assert_eq!(film.pixel(1, 0), Colour::white());
+        assert_eq!(film.pixel(1, 1), grey);
+
+        let film = StereoLayout::Anaglyph.combine(&left, &right);
+        assert_eq!((film.width(), film.height()), (2, 1));
+        assert_eq!(film.pixel(0, 0), Colour::new(1.0, 0.5, 0.5));
+    }
+}
@@ -0,0 +1,303 @@
+//! Constructive solid geometry.
+//!
+//! Solid objects are combined like sets of points: a union is inside either
+//! object, an intersection is inside both, and a difference is inside the
+//! first but not the second. This builds shapes that would be hard to model
+//! otherwise, like a block with a hole drilled through it, or a lens from the
+//! overlap of two spheres.
+//!
+//! Each object finds the [spans](crate::geometry::Geometry::spans) of a ray
+//! that are inside it, and these are merged along the ray. The objects should
+//! be closed, with outward normals, and combinations can be combined again.
+
+use crate::geometry::{BoundingBox, Geometry, Hit, Span, EPSILON};
+use crate::material::Material;
+use crate::utility::Ray;
+
+/// How the objects of a [`Csg`] are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Everything inside either object.
+    Union,
+    /// Everything inside both objects.
+    Intersection,
+    /// Everything inside the first object, but not the second.
+    Difference,
+}
+
+impl Operation {
+    /// Whether a point is inside the combination, given whether it's inside
+    /// each object.
+    fn contains(self, a: bool, b: bool) -> bool {
+        match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        }
+    }
+}
+
+/// Two solid objects combined into one.
+///
+/// The surface of each part keeps the material of the object it came from.
+/// In a difference, the surface carved out by the second object faces into
+/// it. Combinations can't be used as lights.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::csg::Csg;
+/// use raytracer::geometry::{Cuboid, Geometry, Sphere};
+/// use raytracer::material::Matte;
+/// use raytracer::utility::{Colour, Ray, Vec3};
+///
+/// let material = Matte::new(0.25, 0.65, Colour::white());
+/// let cube = Cuboid::new(
+///     Vec3::new(-1.0, -1.0, -1.0),
+///     Vec3::new(1.0, 1.0, 1.0),
+///     material.clone(),
+/// );
+/// let sphere = Sphere {
+///     centre: Vec3::new(0.0, 0.0, 1.0),
+///     radius: 0.5,
+///     material,
+/// };
+///
+/// // a cube with a dent scooped out of the front
+/// let dented = Csg::difference(cube, sphere);
+///
+/// let ray = Ray {
+///     origin: Vec3::new(0.0, 0.0, 5.0),
+///     direction: Vec3::new(0.0, 0.0, -1.0),
+/// };
+/// let hit = dented.hit(&ray).unwrap();
+/// assert!((hit.t - 4.5).abs() < 1e-9);
+/// assert!((hit.normal - Vec3::new(0.0, 0.0, 1.0)).mag() < 1e-9);
+/// ```
+#[derive(Debug)]
+pub struct Csg<A: Geometry, B: Geometry> {
+    pub a: A,
+    pub b: B,
+    pub operation: Operation,
+}
+
+impl<A: Geometry, B: Geometry> Csg<A, B> {
+    pub fn new(a: A, b: B, operation: Operation) -> Self {
+        Self { a, b, operation }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Self::new(a, b, Operation::Union)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Self::new(a, b, Operation::Intersection)
+    }
+
+    /// Cuts `b` out of `a`.
+    pub fn difference(a: A, b: B) -> Self {
+        Self::new(a, b, Operation::Difference)
+    }
+}
+
+impl<A: Geometry, B: Geometry> Geometry for Csg<A, B> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .flatten()
+            .find(|hit| hit.t > EPSILON)
+    }
+
+    /// The material of the first object.
+    fn material(&self) -> &dyn Material {
+        self.a.material()
+    }
+
+    /// An intersection of objects whose boxes don't overlap is empty, so like
+    /// a mesh without any faces it has an empty box.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let a = self.a.bounding_box();
+        let b = self.b.bounding_box();
+        match self.operation {
+            Operation::Union => Some(a?.union(b?)),
+            Operation::Intersection => match (a, b) {
+                (Some(a), Some(b)) => {
+                    let overlap = BoundingBox::new(a.min.max(b.min), a.max.min(b.max));
+                    if overlap.is_empty() {
+                        Some(BoundingBox::empty())
+                    } else {
+                        Some(overlap)
+                    }
+                },
+                (a, b) => a.or(b),
+            },
+            Operation::Difference => a,
+        }
+    }
+
+    /// Walks along the ray through every boundary of either object, keeping
+    /// track of which objects it's inside, and notes wherever that changes
+    /// whether it's inside the combination.
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let spans = [self.a.spans(ray), self.b.spans(ray)];
+
+        let mut inside = [false; 2];
+        let mut boundaries = Vec::new();
+        for (object, spans) in spans.iter().enumerate() {
+            inside[object] = spans.first().is_some_and(|span| span.enter.is_none());
+            for span in spans {
+                boundaries.extend(span.enter.map(|hit| (hit, object, true)));
+                boundaries.extend(span.exit.map(|hit| (hit, object, false)));
+            }
+        }
+        boundaries.sort_by(|(a, ..), (b, ..)| a.t.total_cmp(&b.t));
+
+        let mut result = Vec::new();
+        let mut was_inside = self.operation.contains(inside[0], inside[1]);
+        let mut enter = None;
+        for (mut hit, object, entering) in boundaries {
+            inside[object] = entering;
+            let is_inside = self.operation.contains(inside[0], inside[1]);
+            if is_inside == was_inside {
+                continue;
+            }
+            was_inside = is_inside;
+
+            // the inside of the second object is the outside of a difference
+            if self.operation == Operation::Difference && object == 1 {
+                hit.normal = -hit.normal;
+            }
+            if is_inside {
+                enter = Some(hit);
+            } else {
+                result.push(Span {
+                    enter: enter.take(),
+                    exit: Some(hit),
+                });
+            }
+        }
+
+        if was_inside {
+            result.push(Span { enter, exit: None });
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::material::Matte;
+    use crate::utility::{Colour, Vec3};
+
+    /// The distance to one end of a span, and the `z` part of its normal.
+    type End = Option<(f64, f64)>;
+
+    /// A unit sphere on the `z` axis.
+    fn sphere(material: &Matte, z: f64) -> Sphere<Matte> {
+        Sphere {
+            centre: Vec3::new(0.0, 0.0, z),
+            radius: 1.0,
+            material: material.clone(),
+        }
+    }
+
+    /// Unit spheres at the origin and one unit along `z`, combined.
+    fn spheres(
+        material: &Matte,
+        operation: Operation,
+    ) -> Csg<Sphere<Matte>, Sphere<Matte>> {
+        Csg::new(sphere(material, 0.0), sphere(material, 1.0), operation)
+    }
+
+    /// A ray back along the `z` axis from `z`.
+    fn ray_from(z: f64) -> Ray {
+        Ray {
+            origin: Vec3::new(0.0, 0.0, z),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        }
+    }
+
+    fn span_ends(csg: &impl Geometry, ray: &Ray) -> Vec<(End, End)> {
+        let end = |hit: Option<Hit<'_>>| hit.map(|hit| (hit.t, hit.normal.z));
+        csg.spans(ray)
+            .into_iter()
+            .map(|span| (end(span.enter), end(span.exit)))
+            .collect()
+    }
+
+    #[test]
+    fn spans_from_outside() {
+        let material = Matte::new(0.25, 0.65, Colour::white());
+        let ray = ray_from(5.0);
+        let spans = |operation| span_ends(&spheres(&material, operation), &ray);
+
+        // the origin's sphere covers 4 to 6, the other 3 to 5
+        assert_eq!(
+            spans(Operation::Union),
+            vec![(Some((3.0, 1.0)), Some((6.0, -1.0)))]
+        );
+        assert_eq!(
+            spans(Operation::Intersection),
+            vec![(Some((4.0, 1.0)), Some((5.0, -1.0)))]
+        );
+        // the carved out surface faces into the second sphere
+        assert_eq!(
+            spans(Operation::Difference),
+            vec![(Some((5.0, 1.0)), Some((6.0, -1.0)))]
+        );
+        let reversed = Csg::difference(sphere(&material, 1.0), sphere(&material, 0.0));
+        assert_eq!(
+            span_ends(&reversed, &ray),
+            vec![(Some((3.0, 1.0)), Some((4.0, -1.0)))]
+        );
+
+        // and missing both
+        let miss = Ray {
+            origin: Vec3::new(3.0, 0.0, 5.0),
+            ..ray
+        };
+        let union = spheres(&material, Operation::Union);
+        assert!(span_ends(&union, &miss).is_empty());
+    }
+
+    #[test]
+    fn spans_from_inside() {
+        let material = Matte::new(0.25, 0.65, Colour::white());
+        // from inside both spheres; the first is left after 1.5, the second
+        // after 0.5
+        let ray = ray_from(0.5);
+        let spans = |operation| span_ends(&spheres(&material, operation), &ray);
+
+        assert_eq!(spans(Operation::Union), vec![(None, Some((1.5, -1.0)))]);
+        assert_eq!(
+            spans(Operation::Intersection),
+            vec![(None, Some((0.5, -1.0)))]
+        );
+        assert_eq!(
+            spans(Operation::Difference),
+            vec![(Some((0.5, 1.0)), Some((1.5, -1.0)))]
+        );
+
+        // hits are where the ray crosses the surface, inside or out
+        let hit = |operation| spheres(&material, operation).hit(&ray).unwrap().t;
+        assert_eq!(hit(Operation::Union), 1.5);
+        assert_eq!(hit(Operation::Intersection), 0.5);
+        assert_eq!(hit(Operation::Difference), 0.5);
+    }
+
+    #[test]
+    fn disjoint_intersection_is_empty() {
+        let material = Matte::new(0.25, 0.65, Colour::white());
+        let csg = Csg::intersection(sphere(&material, -2.0), sphere(&material, 2.0));
+        assert!(csg.bounding_box().unwrap().is_empty());
+        assert!(csg.hit(&ray_from(5.0)).is_none());
+
+        let csg = Csg::intersection(sphere(&material, -0.5), sphere(&material, 0.5));
+        let bbox = csg.bounding_box().unwrap();
+        assert_eq!(bbox.min, Vec3::new(-1.0, -1.0, -0.5));
+        assert_eq!(bbox.max, Vec3::new(1.0, 1.0, 0.5));
+    }
+}
@@ -0,0 +1,202 @@
+//! Lighting from an image of the surroundings.
+//!
+//! An [`EnvironmentMap`] wraps the whole world in a high dynamic range image,
+//! like a photo of the sky. It's seen by any ray that doesn't hit anything,
+//! and with an [`EnvironmentLight`](crate::light::EnvironmentLight) it also
+//! lights the world, with brighter parts of the image sampled more often.
+
+use crate::film::Film;
+use crate::utility::{Colour, Vec2, Vec3};
+
+use std::f64::consts;
+
+/// An equirectangular image covering every direction.
+///
+/// The top of the image is straight up, along `+y`, and the bottom is straight
+/// down. The centre of the image looks along `-z`, before any rotation.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::environment::EnvironmentMap;
+/// use raytracer::film::Film;
+/// use raytracer::utility::{Colour, Vec2, Vec3};
+///
+/// // a bright sky above a dark ground
+/// let mut film = Film::new(4, 2);
+/// for x in 0..4 {
+///     film.add_sample(x, 0, Colour::new(4.0, 4.0, 4.0), 1.0);
+///     film.add_sample(x, 1, Colour::new(0.1, 0.1, 0.1), 1.0);
+/// }
+/// let map = EnvironmentMap::new(&film, 0.0, 1.0);
+///
+/// let up = Vec3::new(0.0, 1.0, 0.0);
+/// assert_eq!(map.radiance(up), Colour::new(4.0, 4.0, 4.0));
+///
+/// // the bright half is far more likely to be chosen
+/// let (direction, _) = map.sample_direction(Vec2::new(0.5, 0.5));
+/// assert!(direction.y > 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap {
+    /// Rotation of the image about the vertical axis, in degrees.
+    pub rotation: f64,
+    /// Multiplier for the brightness of the image.
+    pub scale: f64,
+    width: usize,
+    height: usize,
+    /// The colour of every pixel, in row-major order.
+    pixels: Vec<Colour>,
+    /// Cumulative distribution for choosing a row.
+    rows: Vec<f64>,
+    /// Cumulative distributions for choosing a pixel in each row, one after
+    /// another.
+    columns: Vec<f64>,
+    /// Probability of choosing each pixel.
+    probabilities: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    /// Creates an environment map from an image.
+    ///
+    /// Pixels are sampled in proportion to their luminance, weighted by the
+    /// solid angle they cover.
+    pub fn new(film: &Film, rotation: f64, scale: f64) -> Self {
+        let width = film.width() as usize;
+        let height = film.height() as usize;
+        let pixels: Vec<Colour> = film.pixels().map(|(_, _, colour)| colour).collect();
+
+        // rows near the poles cover less of the sphere
+        let sin_theta = |y: usize| (consts::PI * (y as f64 + 0.5) / height as f64).sin();
+        let mut weights: Vec<f64> = pixels
+            .iter()
+            .enumerate()
+            .map(|(i, colour)| colour.luminance().max(0.0) * sin_theta(i / width))
+            .collect();
+        if weights.iter().sum::<f64>() <= 0.0 {
+            // nothing to importance sample, so sample evenly instead
+            weights = (0..pixels.len()).map(|i| sin_theta(i / width)).collect();
+        }
+        let total: f64 = weights.iter().sum();
+
+        let mut rows = Vec::with_capacity(height);
+        let mut columns = Vec::with_capacity(pixels.len());
+        let mut accum = 0.0;
+        for row in weights.chunks(width) {
+            let row_total: f64 = row.iter().sum();
+            accum += row_total / total;
+            rows.push(accum);
+            columns.extend(cumulative(row, row_total));
+        }
+        let probabilities = weights.iter().map(|w| w / total).collect();
+
+        Self {
+            rotation,
+            scale,
+            width,
+            height,
+            pixels,
+            rows,
+            columns,
+            probabilities,
+        }
+    }
+
+    /// The radiance arriving from the given direction, which must be
+    /// normalised.
+    pub fn radiance(&self, direction: Vec3) -> Colour {
+        let (x, y) = self.pixel(self.image_at(direction));
+        self.scale * self.pixels[y * self.width + x]
+    }
+
+    /// Chooses a direction, in proportion to the brightness of the image, from
+    /// a sample on the unit square.
+    ///
+    /// Returns the direction and its probability density, by solid angle.
+    pub fn sample_direction(&self, sample: Vec2) -> (Vec3, f64) {
+        let (y, v) = choose(&self.rows, sample.x);
+        let row = &self.columns[y * self.width..(y + 1) * self.width];
+        let (x, u) = choose(row, sample.y);
+
+        let uv = Vec2::new(
+            (x as f64 + u) / self.width as f64,
+            (y as f64 + v) / self.height as f64,
+        );
+        let direction = self.direction_at(uv);
+
+        // the density over the image, converted to the density over the sphere
+        let density = self.probabilities[y * self.width + x] * self.pixels.len() as f64;
+        let sin_theta = (consts::PI * uv.y).sin();
+        let pdf = if sin_theta > 0.0 {
+            density / (2.0 * consts::PI * consts::PI * sin_theta)
+        } else {
+            0.0
+        };
+        (direction, pdf)
+    }
+
+    /// Converts a direction to coordinates on the image, from `(0, 0)` at the
+    /// top left to `(1, 1)` at the bottom right.
+    fn image_at(&self, direction: Vec3) -> Vec2 {
+        let d = rotate(direction, -self.rotation);
+        let u = 0.5 + d.x.atan2(-d.z) * 0.5 * consts::FRAC_1_PI;
+        let v = d.y.clamp(-1.0, 1.0).acos() * consts::FRAC_1_PI;
+        Vec2::new(u, v)
+    }
+
+    /// The inverse of [`image_at`](EnvironmentMap::image_at).
+    fn direction_at(&self, uv: Vec2) -> Vec3 {
+        let phi = 2.0 * consts::PI * (uv.x - 0.5);
+        let theta = consts::PI * uv.y;
+        let d = Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+        rotate(d, self.rotation)
+    }
+
+    /// The pixel containing the given coordinates on the image.
+    fn pixel(&self, uv: Vec2) -> (usize, usize) {
+        let x = (uv.x * self.width as f64) as usize;
+        let y = (uv.y * self.height as f64) as usize;
+        (x.min(self.width - 1), y.min(self.height - 1))
+    }
+}
+
+/// Rotates a vector about the `y` axis by an angle in degrees.
+fn rotate(v: Vec3, degrees: f64) -> Vec3 {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    Vec3::new(v.x * cos + v.z * sin, v.y, -v.x * sin + v.z * cos)
+}
+
+/// The normalised running totals of `weights`, which sum to `total`.
+///
+/// If they're all zero they're treated as being equal.
+fn cumulative(weights: &[f64], total: f64) -> impl Iterator<Item = f64> + '_ {
+    let n = weights.len() as f64;
+    weights.iter().enumerate().scan(0.0, move |accum, (i, w)| {
+        *accum += w;
+        Some(if total > 0.0 {
+            *accum / total
+        } else {
+            (i + 1) as f64 / n
+        })
+    })
+}
+
+/// Chooses an index from a cumulative distribution using `x` in `[0, 1)`.
+///
+/// Also returns where `x` falls within the chosen index's part of the
+/// distribution, from `0` to `1`, so the rest of the sample can be reused.
+fn choose(cdf: &[f64], x: f64) -> (usize, f64) {
+    let i = cdf.partition_point(|&c| c <= x).min(cdf.len() - 1);
+    let start = if i == 0 { 0.0 } else { cdf[i - 1] };
+    let width = cdf[i] - start;
+    let offset = if width > 0.0 {
+        (x - start) / width
+    } else {
+        0.5
+    };
+    (i, offset.clamp(0.0, 1.0))
+}
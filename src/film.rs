@@ -0,0 +1,389 @@
+//! Storage for rendered images.
+//!
+//! Cameras render into a [`Film`], which keeps the full floating-point
+//! radiance of every pixel. Converting to an 8-bit image for display is a
+//! separate step, so that no information is thrown away until the image is
+//! actually saved.
+//!
+//! Films can be [saved](Film::save) in high dynamic range formats, keeping
+//! the linear, unclamped radiance, or in any 8-bit format supported by the
+//! [`image`] crate like PNG. Images in any of these formats can also be
+//! [loaded](Film::load) as films, for use as textures or environment maps.
+
+use crate::tonemap::{Display, Gamma};
+use crate::utility::Colour;
+
+use image::codecs::hdr::{HdrDecoder, HdrEncoder};
+use image::{GenericImageView, ImageError, Rgb, RgbImage, Rgba};
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// An error from loading or saving a film.
+#[derive(Debug)]
+pub enum Error {
+    /// The file couldn't be read or written, or isn't a valid portable float
+    /// map.
+    Io { path: PathBuf, source: io::Error },
+    /// The image couldn't be decoded or encoded, or the format isn't
+    /// supported.
+    Image { path: PathBuf, source: ImageError },
+    /// The OpenEXR image couldn't be read or written.
+    Exr {
+        path: PathBuf,
+        source: exr::error::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Exr { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Image { source, .. } => Some(source),
+            Error::Exr { source, .. } => Some(source),
+        }
+    }
+}
+
+/// The file formats a film can be loaded from and saved in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// OpenEXR, with 32-bit float channels.
+    Exr,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map.
+    Pfm,
+    /// Any 8-bit format supported by the [`image`] crate.
+    Ldr,
+}
+
+impl Format {
+    /// Chooses the format based on the extension of `path`.
+    ///
+    /// Anything not recognised as a high dynamic range format is assumed to
+    /// be an 8-bit format.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raytracer::film::Format;
+    ///
+    /// assert_eq!(Format::from_path("render.EXR"), Format::Exr);
+    /// assert_eq!(Format::from_path("render.png"), Format::Ldr);
+    /// ```
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("exr") => Format::Exr,
+            Some("hdr") => Format::Hdr,
+            Some("pfm") => Format::Pfm,
+            _ => Format::Ldr,
+        }
+    }
+}
+
+/// A floating-point image buffer.
+///
+/// Each pixel accumulates any number of weighted samples, and its value is
+/// their weighted average. Pixels are indexed from the top left corner.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::film::Film;
+/// use raytracer::utility::Colour;
+///
+/// let mut film = Film::new(2, 1);
+/// film.add_sample(0, 0, Colour::new(1.0, 2.0, 4.0), 1.0);
+/// film.add_sample(0, 0, Colour::new(3.0, 2.0, 0.0), 1.0);
+///
+/// // values aren't clamped, and unsampled pixels are black
+/// assert_eq!(film.pixel(0, 0), Colour::new(2.0, 2.0, 2.0));
+/// assert_eq!(film.pixel(1, 0), Colour::black());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Film {
+    width: u32,
+    height: u32,
+    /// Weighted sum of the samples of each pixel, in row-major order.
+    sums: Vec<Colour>,
+    /// Total weight of the samples of each pixel.
+    weights: Vec<f64>,
+}
+
+impl Film {
+    /// Creates a new, entirely black film.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            sums: vec![Colour::black(); len],
+            weights: vec![0.0; len],
+        }
+    }
+
+    /// The width of the image, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the image, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Adds a sample with the given weight to a pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel is outside of the film.
+    pub fn add_sample(&mut self, x: u32, y: u32, colour: Colour, weight: f64) {
+        let i = self.index(x, y);
+        self.sums[i] = self.sums[i] + colour * weight;
+        self.weights[i] += weight;
+    }
+
+    /// The colour of a pixel: the weighted average of its samples.
+    ///
+    /// Pixels without any samples are black.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel is outside of the film.
+    pub fn pixel(&self, x: u32, y: u32) -> Colour {
+        let i = self.index(x, y);
+        if self.weights[i] > 0.0 {
+            self.sums[i] / self.weights[i]
+        } else {
+            Colour::black()
+        }
+    }
+
+    /// Iterates over the colours of every pixel, along with their positions,
+    /// in row-major order.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32, Colour)> + '_ {
+        let width = self.width;
+        (0..self.sums.len()).map(move |i| {
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            (x, y, self.pixel(x, y))
+        })
+    }
+
+    /// Loads an image from `path`, in the [format](Format::from_path) given by
+    /// its extension.
+    ///
+    /// High dynamic range formats are loaded as they are, while 8-bit images
+    /// are assumed to be in the sRGB colour space, and converted to linear
+    /// colours.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let io_error = |source| Error::Io {
+            path: path.to_owned(),
+            source,
+        };
+        let image_error = |source| Error::Image {
+            path: path.to_owned(),
+            source,
+        };
+
+        match Format::from_path(path) {
+            Format::Exr => {
+                let create = |size: exr::math::Vec2<usize>, _: &_| {
+                    Film::new(size.width() as u32, size.height() as u32)
+                };
+                let set = |film: &mut Film,
+                           position: exr::math::Vec2<usize>,
+                           (r, g, b, _): (f32, f32, f32, f32)| {
+                    let colour = Colour::new(r.into(), g.into(), b.into());
+                    film.add_sample(
+                        position.x() as u32,
+                        position.y() as u32,
+                        colour,
+                        1.0,
+                    );
+                };
+                exr::prelude::read_first_rgba_layer_from_file(path, create, set)
+                    .map(|image| image.layer_data.channel_data.pixels)
+                    .map_err(|source| Error::Exr {
+                        path: path.to_owned(),
+                        source,
+                    })
+            },
+            Format::Hdr => {
+                let file = File::open(path).map_err(io_error)?;
+                let decoder =
+                    HdrDecoder::new(BufReader::new(file)).map_err(image_error)?;
+                let metadata = decoder.metadata();
+                let data = decoder.read_image_hdr().map_err(image_error)?;
+                let mut film = Film::new(metadata.width, metadata.height);
+                for (i, Rgb([r, g, b])) in data.into_iter().enumerate() {
+                    let colour = Colour::new(r.into(), g.into(), b.into());
+                    let (x, y) = (i as u32 % film.width, i as u32 / film.width);
+                    film.add_sample(x, y, colour, 1.0);
+                }
+                Ok(film)
+            },
+            Format::Pfm => {
+                let file = File::open(path).map_err(io_error)?;
+                Film::read_pfm(BufReader::new(file)).map_err(io_error)
+            },
+            Format::Ldr => {
+                let image = image::open(path).map_err(image_error)?;
+                let mut film = Film::new(image.width(), image.height());
+                for (x, y, Rgba(rgb)) in image.pixels() {
+                    let channel = |c: u8| Gamma::Srgb.decode(f64::from(c) / 255.0);
+                    let colour =
+                        Colour::new(channel(rgb[0]), channel(rgb[1]), channel(rgb[2]));
+                    film.add_sample(x, y, colour, 1.0);
+                }
+                Ok(film)
+            },
+        }
+    }
+
+    /// Converts the film to an 8-bit image, using the given display
+    /// settings.
+    pub fn to_rgb(&self, display: &Display) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            display.to_rgb(self.pixel(x, y))
+        })
+    }
+
+    /// Saves the film to `path`, in the [format](Format::from_path) given by
+    /// its extension.
+    ///
+    /// The `display` settings are only used for 8-bit formats: high dynamic
+    /// range formats store the linear colours as they are.
+    pub fn save(&self, path: impl AsRef<Path>, display: &Display) -> Result<(), Error> {
+        let path = path.as_ref();
+        let io_error = |source| Error::Io {
+            path: path.to_owned(),
+            source,
+        };
+
+        match Format::from_path(path) {
+            Format::Exr => {
+                let pixel = |x: usize, y: usize| {
+                    let c = self.pixel(x as u32, y as u32);
+                    (c.r as f32, c.g as f32, c.b as f32)
+                };
+                let (width, height) = (self.width as usize, self.height as usize);
+                exr::prelude::write_rgb_file(path, width, height, pixel).map_err(
+                    |source| Error::Exr {
+                        path: path.to_owned(),
+                        source,
+                    },
+                )
+            },
+            Format::Hdr => {
+                let file = File::create(path).map_err(io_error)?;
+                let data = self
+                    .pixels()
+                    .map(|(_, _, c)| Rgb([c.r as f32, c.g as f32, c.b as f32]))
+                    .collect::<Vec<_>>();
+                let (width, height) = (self.width as usize, self.height as usize);
+                let mut writer = BufWriter::new(file);
+                HdrEncoder::new(&mut writer)
+                    .encode(&data, width, height)
+                    .map_err(|source| Error::Image {
+                        path: path.to_owned(),
+                        source,
+                    })?;
+                // dropping the writer would flush it, but ignore any error
+                writer.flush().map_err(io_error)
+            },
+            Format::Pfm => {
+                let file = File::create(path).map_err(io_error)?;
+                self.write_pfm(BufWriter::new(file)).map_err(io_error)
+            },
+            Format::Ldr => {
+                self.to_rgb(display)
+                    .save(path)
+                    .map_err(|source| Error::Image {
+                        path: path.to_owned(),
+                        source,
+                    })
+            },
+        }
+    }
+
+    /// Writes the film as a little-endian portable float map.
+    fn write_pfm(&self, mut w: impl Write) -> io::Result<()> {
+        write!(w, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        // rows go from the bottom up
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.pixel(x, y);
+                for channel in &[c.r, c.g, c.b] {
+                    w.write_all(&(*channel as f32).to_le_bytes())?;
+                }
+            }
+        }
+        w.flush()
+    }
+
+    /// Reads a portable float map, in either byte order.
+    fn read_pfm(mut r: impl BufRead) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        // the header is three whitespace-separated tokens after the magic
+        let mut header = Vec::new();
+        while header.len() < 4 {
+            let mut line = String::new();
+            if r.read_line(&mut line)? == 0 {
+                return Err(invalid("truncated portable float map header"));
+            }
+            header.extend(line.split_whitespace().map(str::to_owned));
+        }
+        if header[0] != "PF" {
+            return Err(invalid("only colour portable float maps are supported"));
+        }
+        let parse_size = |s: &str| s.parse().map_err(|_| invalid("invalid image size"));
+        let (width, height): (u32, u32) =
+            (parse_size(&header[1])?, parse_size(&header[2])?);
+        let scale: f32 = header[3].parse().map_err(|_| invalid("invalid scale"))?;
+
+        let mut film = Film::new(width, height);
+        let mut bytes = [0; 4];
+        let mut channel = || -> io::Result<f64> {
+            r.read_exact(&mut bytes)?;
+            let value = if scale < 0.0 {
+                f32::from_le_bytes(bytes)
+            } else {
+                f32::from_be_bytes(bytes)
+            };
+            Ok(value.into())
+        };
+        // rows go from the bottom up
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let colour = Colour::new(channel()?, channel()?, channel()?);
+                film.add_sample(x, y, colour, 1.0);
+            }
+        }
+        Ok(film)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(x < self.width && y < self.height, "pixel outside of film");
+        y as usize * self.width as usize + x as usize
+    }
+}
@@ -1,29 +1,244 @@
 //! Different kinds of objects in the world.
 
+use crate::brdf;
 use crate::material::Material;
-use crate::utility::{Ray, Vec3};
-use std::fmt;
+use crate::utility::{Ray, Transform, Vec2, Vec3};
+use std::sync::Arc;
+use std::{f64, fmt};
 
 /// Used to ignore rounding errors, and prevent contact with camera.
-const EPSILON: f64 = 0.0001;
+pub(crate) const EPSILON: f64 = 0.0001;
+
+/// Details of where a ray hits some geometry.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit<'a> {
+    /// The distance along the ray to the hit point, in multiples of the ray's
+    /// direction.
+    pub t: f64,
+    /// The normalised normal of the surface at the hit point, facing outwards.
+    pub normal: Vec3,
+    /// The material of the surface that was hit.
+    pub material: &'a dyn Material,
+}
+
+/// A stretch of a ray which is inside a solid object.
+///
+/// Used for [constructive solid geometry](crate::csg), which combines the
+/// spans of its objects.
+#[derive(Debug, Clone, Copy)]
+pub struct Span<'a> {
+    /// Where the ray enters the object, or `None` if it starts inside.
+    pub enter: Option<Hit<'a>>,
+    /// Where the ray leaves the object, or `None` if it never does.
+    pub exit: Option<Hit<'a>>,
+}
 
 /// Interface trait for objects with geometry.
 ///
-/// If the given ray hits the geometry, writes into `shader` and returns
-/// the distance along the ray the collision occurred. Otherwise returns `None`.
-pub trait Geometry: fmt::Debug {
+/// If the given ray hits the geometry, returns the distance along the ray the
+/// collision occurred, along with the surface's normal and material there.
+/// Otherwise returns `None`.
+pub trait Geometry: fmt::Debug + Send + Sync {
     /// If the ray will collide with this geometry, returns details on the
     /// intersection.
-    fn hit(&self, ray: &Ray) -> Option<(f64, &dyn Geometry)>;
-
-    /// Returns the normal on the geometry at the given point.
     ///
-    /// Assumes the point is in fact (approximately) on the geometry, though it
-    /// is largely unlikely to matter.
-    fn normal(&self, pos: Vec3) -> Vec3;
+    /// The ray's direction need not be normalised. Hits closer than
+    /// `EPSILON`, relative to the direction's length, are ignored.
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>>;
 
     /// Get the material associated with the object.
     fn material(&self) -> &dyn Material;
+
+    /// Returns an axis-aligned box enclosing the geometry.
+    ///
+    /// Unbounded geometry (like a [`Plane`]) returns `None`, and will not be
+    /// placed in any [bounding volume hierarchy](crate::bvh::Bvh). Geometry
+    /// which can never be hit, like a mesh without any faces, returns an
+    /// [empty](BoundingBox::is_empty) box, and is left out altogether.
+    fn bounding_box(&self) -> Option<BoundingBox>;
+
+    /// Chooses a point on the surface, so that the geometry can be used as a
+    /// [light](crate::light::GeometryLight).
+    ///
+    /// The sample on the unit square is mapped to a point chosen uniformly by
+    /// area. Returns the point, the outward normal there, and the probability
+    /// density of choosing it, which is one over the surface area. Geometry
+    /// which can't be sampled, like an infinite [`Plane`], returns `None`,
+    /// which is the default.
+    fn sample_surface(&self, _sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        None
+    }
+
+    /// Finds every stretch of the ray which is inside the geometry, in order
+    /// along the ray.
+    ///
+    /// Like [`hit`](Geometry::hit), hits closer than `EPSILON` are ignored,
+    /// so a span which started before that has no `enter`. By default the
+    /// ray is followed from hit to hit, entering wherever the normal faces
+    /// against it and leaving wherever it faces along it. This only makes
+    /// sense for closed objects with outward normals, and not flat ones like
+    /// a [`Disc`].
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let mut spans = Vec::new();
+        let mut enter = None;
+        let mut offset = 0.0;
+
+        let next = |offset| Ray {
+            origin: ray.at(offset),
+            direction: ray.direction,
+        };
+        while let Some(hit) = self.hit(&next(offset)) {
+            offset += hit.t;
+            let hit = Hit { t: offset, ..hit };
+            if hit.normal.dot(ray.direction) < 0.0 {
+                enter = Some(hit);
+            } else {
+                spans.push(Span {
+                    enter: enter.take(),
+                    exit: Some(hit),
+                });
+            }
+        }
+
+        if enter.is_some() {
+            spans.push(Span { enter, exit: None });
+        }
+        spans
+    }
+}
+
+/// Allows a single object to be shared, like between the world's objects and
+/// its lights.
+impl<G: Geometry + ?Sized> Geometry for Arc<G> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        (**self).hit(ray)
+    }
+
+    fn material(&self) -> &dyn Material {
+        (**self).material()
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        (**self).bounding_box()
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        (**self).sample_surface(sample)
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        (**self).spans(ray)
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    /// Min point.
+    pub min: Vec3,
+    /// Max point.
+    pub max: Vec3,
+}
+
+impl BoundingBox {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// A box containing nothing at all.
+    ///
+    /// This is the identity for [`BoundingBox::union`].
+    pub fn empty() -> Self {
+        let inf = f64::INFINITY;
+        Self {
+            min: Vec3::new(inf, inf, inf),
+            max: Vec3::new(-inf, -inf, -inf),
+        }
+    }
+
+    /// Whether the box contains nothing at all, because its minimum is past
+    /// its maximum along some axis.
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(self, rhs: Self) -> Self {
+        Self {
+            min: self.min.min(rhs.min),
+            max: self.max.max(rhs.max),
+        }
+    }
+
+    /// The smallest box containing both this box and the given point.
+    pub fn include(self, point: Vec3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    /// The centre point of the box.
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// The total surface area of the box.
+    ///
+    /// Empty boxes have a surface area of zero.
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            0.0
+        } else {
+            2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+        }
+    }
+
+    /// The index of the axis along which the box is longest.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns the range of distances along the ray for which the ray is
+    /// within the box, using slab intersection.
+    ///
+    /// `inv_dir` must be the reciprocal of the ray's direction; it is passed in
+    /// separately so that it can be calculated once for many boxes. Only
+    /// intersections within `(0, t_max)` are reported.
+    pub fn intersect(&self, ray: &Ray, inv_dir: Vec3, t_max: f64) -> Option<(f64, f64)> {
+        let mut t_near = 0.0_f64;
+        let mut t_far = t_max;
+
+        for axis in 0..3 {
+            // a ray parallel to the slab is either always in it or never, and
+            // would otherwise give NaN if it starts on the edge
+            if ray.direction[axis] == 0.0 {
+                let o = ray.origin[axis];
+                if o < self.min[axis] || o > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t_1 = (self.min[axis] - ray.origin[axis]) * inv_dir[axis];
+            let t_2 = (self.max[axis] - ray.origin[axis]) * inv_dir[axis];
+            t_near = t_near.max(t_1.min(t_2));
+            t_far = t_far.min(t_1.max(t_2));
+        }
+
+        if t_near <= t_far {
+            Some((t_near, t_far))
+        } else {
+            None
+        }
+    }
 }
 
 /// An infinite plane.
@@ -42,11 +257,21 @@ pub struct Sphere<M: Material> {
     pub material: M,
 }
 
-/// An axis-aligned cuboid.
+/// A single triangle.
 ///
-/// # TODO
+/// The vertices should be specified in counter-clockwise order when viewed
+/// from the front of the triangle; the normal points towards the viewer.
+#[derive(Debug)]
+pub struct Triangle<M: Material> {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub material: M,
+}
+
+/// An axis-aligned cuboid.
 ///
-/// Add support for being, well, not axis-aligned.
+/// To turn it some other way, place it with an [`Instance`].
 #[derive(Debug)]
 pub struct Cuboid<M: Material> {
     /// Min point.
@@ -69,30 +294,226 @@ impl<M: Material> Cuboid<M> {
             material,
         }
     }
+
+    /// The normal of the face containing the given point.
+    fn normal(&self, pos: Vec3) -> Vec3 {
+        let centre = (self.min + self.max) * 0.5;
+        let offset = pos - centre;
+        let divisor = (self.min - self.max) * 0.5;
+        let bias = 1.0 + EPSILON;
+
+        Vec3 {
+            x: (offset.x / divisor.x.abs() * bias).trunc(),
+            y: (offset.y / divisor.y.abs() * bias).trunc(),
+            z: (offset.z / divisor.z.abs() * bias).trunc(),
+        }
+        .normalise()
+    }
+}
+
+/// A flat, round disc.
+///
+/// Like a [`Plane`], it faces the way its `normal` points, which should be
+/// normalised.
+#[derive(Debug)]
+pub struct Disc<M: Material> {
+    pub centre: Vec3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub material: M,
+}
+
+/// A flat ring, like a disc with a hole in the middle.
+///
+/// The `normal` should be normalised.
+#[derive(Debug)]
+pub struct Annulus<M: Material> {
+    pub centre: Vec3,
+    pub normal: Vec3,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    pub material: M,
+}
+
+/// A flat parallelogram, with one corner at `corner` and sides `a` and `b`.
+///
+/// The normal is `a × b`, so the sides should be given counter-clockwise when
+/// viewed from the front. Perpendicular sides make a rectangle.
+#[derive(Debug)]
+pub struct Rectangle<M: Material> {
+    pub corner: Vec3,
+    pub a: Vec3,
+    pub b: Vec3,
+    pub material: M,
+}
+
+/// An upright cylinder around the `y` axis, from `bottom` to `top`.
+///
+/// A closed cylinder is capped at both ends. An open one is just a tube, and
+/// its inside can be seen through the ends; the normal there faces inwards.
+/// To stand it some other way, place it with an [`Instance`].
+#[derive(Debug)]
+pub struct Cylinder<M: Material> {
+    pub radius: f64,
+    pub bottom: f64,
+    pub top: f64,
+    /// Whether the ends are left uncapped.
+    pub open: bool,
+    pub material: M,
+}
+
+/// An upright cone, with its base on the `xz` plane around the origin and its
+/// tip `height` above it.
+///
+/// Closed cones have a base, while open ones can be seen into from below,
+/// like a [`Cylinder`].
+#[derive(Debug)]
+pub struct Cone<M: Material> {
+    /// The radius of the base.
+    pub radius: f64,
+    pub height: f64,
+    /// Whether the base is left uncapped.
+    pub open: bool,
+    pub material: M,
+}
+
+/// A torus lying on the `xz` plane around the origin, like a ring doughnut.
+///
+/// The centre of the tube sweeps around a circle of `swept_radius`, and the
+/// tube itself has a radius of `tube_radius`. Intersecting it means solving a
+/// quartic equation, so it's slower than the other shapes.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::geometry::{Geometry, Torus};
+/// use raytracer::material::Matte;
+/// use raytracer::utility::{Colour, Ray, Vec3};
+///
+/// let torus = Torus::new(2.0, 0.5, Matte::new(0.25, 0.65, Colour::white()));
+///
+/// // straight down through the hole
+/// let ray = Ray {
+///     origin: Vec3::new(0.0, 5.0, 0.0),
+///     direction: Vec3::new(0.0, -1.0, 0.0),
+/// };
+/// assert!(torus.hit(&ray).is_none());
+///
+/// // straight down onto the top of the tube
+/// let ray = Ray {
+///     origin: Vec3::new(2.0, 5.0, 0.0),
+///     direction: Vec3::new(0.0, -1.0, 0.0),
+/// };
+/// let hit = torus.hit(&ray).unwrap();
+/// assert!((hit.t - 4.5).abs() < 1e-9);
+/// assert!((hit.normal - Vec3::new(0.0, 1.0, 0.0)).mag() < 1e-9);
+/// ```
+#[derive(Debug)]
+pub struct Torus<M: Material> {
+    pub swept_radius: f64,
+    pub tube_radius: f64,
+    pub material: M,
+}
+
+impl<M: Material> Cylinder<M> {
+    /// Creates a closed cylinder.
+    pub fn new(radius: f64, bottom: f64, top: f64, material: M) -> Self {
+        Self {
+            radius,
+            bottom,
+            top,
+            open: false,
+            material,
+        }
+    }
+
+    /// Creates a cylinder without end caps.
+    pub fn open(radius: f64, bottom: f64, top: f64, material: M) -> Self {
+        Self {
+            open: true,
+            ..Self::new(radius, bottom, top, material)
+        }
+    }
+}
+
+impl<M: Material> Cone<M> {
+    /// Creates a cone with a base.
+    pub fn new(radius: f64, height: f64, material: M) -> Self {
+        Self {
+            radius,
+            height,
+            open: false,
+            material,
+        }
+    }
+
+    /// Creates a cone without a base.
+    pub fn open(radius: f64, height: f64, material: M) -> Self {
+        Self {
+            open: true,
+            ..Self::new(radius, height, material)
+        }
+    }
+}
+
+impl<M: Material> Torus<M> {
+    pub fn new(swept_radius: f64, tube_radius: f64, material: M) -> Self {
+        Self {
+            swept_radius,
+            tube_radius,
+            material,
+        }
+    }
 }
 
 impl<M: Material> Geometry for Plane<M> {
-    fn hit(&self, ray: &Ray) -> Option<(f64, &dyn Geometry)> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
         let offset = self.point - ray.origin;
         let t = offset.dot(self.normal) / ray.direction.dot(self.normal);
         if t > EPSILON {
-            Some((t, self))
+            Some(Hit {
+                t,
+                normal: self.normal,
+                material: &self.material,
+            })
         } else {
             None
         }
     }
 
-    fn normal(&self, _pos: Vec3) -> Vec3 {
-        self.normal
-    }
-
     fn material(&self) -> &dyn Material {
         &self.material
     }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        None
+    }
+
+    /// The plane is treated as the boundary of the solid half of space behind
+    /// it, so that it can cut away the side of another object it faces.
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let facing = ray.direction.dot(self.normal);
+        let span = match self.hit(ray) {
+            Some(hit) if facing < 0.0 => Span {
+                enter: Some(hit),
+                exit: None,
+            },
+            Some(hit) => Span {
+                enter: None,
+                exit: Some(hit),
+            },
+            None if (ray.origin - self.point).dot(self.normal) < 0.0 => Span {
+                enter: None,
+                exit: None,
+            },
+            None => return Vec::new(),
+        };
+        vec![span]
+    }
 }
 
 impl<M: Material> Geometry for Sphere<M> {
-    fn hit(&self, ray: &Ray) -> Option<(f64, &dyn Geometry)> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
         let offset = ray.origin - self.centre;
 
         // quadratic equation for "ax^2 + bx + c = 0"
@@ -105,32 +526,172 @@ impl<M: Material> Geometry for Sphere<M> {
             let e = discriminator.sqrt();
             let denominator = 2.0 * a;
 
+            let hit = |t| Hit {
+                t,
+                normal: (ray.at(t) - self.centre).normalise(),
+                material: &self.material,
+            };
+
             let t = (-b - e) / denominator;
             if t > EPSILON {
-                return Some((t, self));
+                return Some(hit(t));
             }
 
             let t = (-b + e) / denominator;
             if t > EPSILON {
-                return Some((t, self));
+                return Some(hit(t));
             }
         }
 
         None
     }
 
-    fn normal(&self, pos: Vec3) -> Vec3 {
-        (pos - self.centre).normalise()
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(BoundingBox::new(self.centre - r, self.centre + r))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let z = 1.0 - 2.0 * sample.x;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * f64::consts::PI * sample.y;
+        let normal = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+        let area = 4.0 * f64::consts::PI * self.radius * self.radius;
+        Some((self.centre + self.radius * normal, normal, 1.0 / area))
+    }
+}
+
+impl<M: Material> Triangle<M> {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: M) -> Self {
+        Self { a, b, c, material }
+    }
+}
+
+/// Intersects a ray with the triangle `(a, b, c)`.
+///
+/// This is the watertight algorithm by Woop, Benthin and Wald: the triangle is
+/// transformed into a space where the ray lies along the `z` axis, so that
+/// rays passing exactly along a shared edge or through a shared vertex always
+/// hit at least one of the adjoining triangles.
+///
+/// Returns the distance along the ray to the hit point.
+pub(crate) fn intersect_triangle(ray: &Ray, a: Vec3, b: Vec3, c: Vec3) -> Option<f64> {
+    let dir = ray.direction;
+
+    // permute the axes so that `z` is the dominant direction of the ray,
+    // preserving the winding direction
+    let (dx, dy, dz) = (dir.x.abs(), dir.y.abs(), dir.z.abs());
+    let kz = if dx > dy && dx > dz {
+        0
+    } else if dy > dz {
+        1
+    } else {
+        2
+    };
+    let (mut kx, mut ky) = ((kz + 1) % 3, (kz + 2) % 3);
+    if dir[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // shear constants
+    let sx = dir[kx] / dir[kz];
+    let sy = dir[ky] / dir[kz];
+    let sz = 1.0 / dir[kz];
+
+    let a = a - ray.origin;
+    let b = b - ray.origin;
+    let c = c - ray.origin;
+
+    let ax = a[kx] - sx * a[kz];
+    let ay = a[ky] - sy * a[kz];
+    let bx = b[kx] - sx * b[kz];
+    let by = b[ky] - sy * b[kz];
+    let cx = c[kx] - sx * c[kz];
+    let cy = c[ky] - sy * c[kz];
+
+    // scaled barycentric coordinates
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return None;
+    }
+
+    let t = (u * sz * a[kz] + v * sz * b[kz] + w * sz * c[kz]) / det;
+    if t > EPSILON {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// Maps a sample on the unit square to a point on the triangle `(a, b, c)`,
+/// chosen uniformly by area.
+pub(crate) fn sample_triangle(sample: Vec2, a: Vec3, b: Vec3, c: Vec3) -> Vec3 {
+    let s = sample.x.sqrt();
+    (1.0 - s) * a + s * (1.0 - sample.y) * b + s * sample.y * c
+}
+
+/// Calculates the barycentric coordinates of `pos` in the triangle `(a, b,
+/// c)`.
+///
+/// Assumes the point is (approximately) on the plane of the triangle.
+pub(crate) fn barycentric(pos: Vec3, a: Vec3, b: Vec3, c: Vec3) -> (f64, f64, f64) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = pos - a;
+
+    let d00 = ab.dot(ab);
+    let d01 = ab.dot(ac);
+    let d11 = ac.dot(ac);
+    let d20 = ap.dot(ab);
+    let d21 = ap.dot(ac);
+    let denominator = d00 * d11 - d01 * d01;
+
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+    (1.0 - v - w, v, w)
+}
+
+impl<M: Material> Geometry for Triangle<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        intersect_triangle(ray, self.a, self.b, self.c).map(|t| Hit {
+            t,
+            normal: (self.b - self.a).cross(self.c - self.a).normalise(),
+            material: &self.material,
+        })
     }
 
     fn material(&self) -> &dyn Material {
         &self.material
     }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let bbox = BoundingBox::empty().include(self.a).include(self.b);
+        Some(bbox.include(self.c))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let point = sample_triangle(sample, self.a, self.b, self.c);
+        let cross = (self.b - self.a).cross(self.c - self.a);
+        let area = 0.5 * cross.mag();
+        Some((point, cross.normalise(), 1.0 / area))
+    }
 }
 
 impl<M: Material> Geometry for Cuboid<M> {
     /// Calculates the intersection point using slab intersection.
-    fn hit(&self, ray: &Ray) -> Option<(f64, &dyn Geometry)> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
         // TODO: include this in the ray itself?
         let invdir = 1.0 / ray.direction;
 
@@ -152,28 +713,866 @@ impl<M: Material> Geometry for Cuboid<M> {
         let t_max = t_xf.min(t_yf.min(t_zf));
 
         if t_min < t_max && t_max > EPSILON {
-            let t = if t_min < 0.0 { t_max } else { t_min };
-            Some((t, self))
+            let t = if t_min > EPSILON { t_min } else { t_max };
+            Some(Hit {
+                t,
+                normal: self.normal(ray.at(t)),
+                material: &self.material,
+            })
         } else {
             None
         }
     }
 
-    fn normal(&self, pos: Vec3) -> Vec3 {
-        let centre = (self.min + self.max) * 0.5;
-        let offset = pos - centre;
-        let divisor = (self.min - self.max) * 0.5;
-        let bias = 1.0 + EPSILON;
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
 
-        Vec3 {
-            x: (offset.x / divisor.x.abs() * bias).trunc(),
-            y: (offset.y / divisor.y.abs() * bias).trunc(),
-            z: (offset.z / divisor.z.abs() * bias).trunc(),
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::empty().include(self.min).include(self.max))
+    }
+
+    /// Chooses an axis in proportion to the area of the faces across it, then
+    /// one of its two faces, and then a point on that face.
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let size = self.max - self.min;
+        let areas = [size.y * size.z, size.x * size.z, size.x * size.y];
+        let total: f64 = areas.iter().sum();
+
+        // the part of the sample left over after choosing an axis is reused
+        let mut x = sample.x * total;
+        let mut axis = 0;
+        while axis < 2 && x >= areas[axis] {
+            x -= areas[axis];
+            axis += 1;
         }
-        .normalise()
+        let x = (x / areas[axis]).min(1.0);
+        let (side, y) = if sample.y < 0.5 {
+            (0.0, 2.0 * sample.y)
+        } else {
+            (1.0, 2.0 * sample.y - 1.0)
+        };
+
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut point = [0.0; 3];
+        let mut normal = [0.0; 3];
+        point[axis] = self.min[axis] + side * size[axis];
+        point[u] = self.min[u] + x * size[u];
+        point[v] = self.min[v] + y * size[v];
+        normal[axis] = 2.0 * side - 1.0;
+
+        Some((
+            Vec3::new(point[0], point[1], point[2]),
+            Vec3::new(normal[0], normal[1], normal[2]),
+            1.0 / (2.0 * total),
+        ))
+    }
+}
+
+/// Intersects a ray with the plane through `point` facing `normal`, returning
+/// the distance along the ray and the hit point.
+fn intersect_plane(ray: &Ray, point: Vec3, normal: Vec3) -> Option<(f64, Vec3)> {
+    let t = (point - ray.origin).dot(normal) / ray.direction.dot(normal);
+    if t > EPSILON {
+        Some((t, ray.at(t)))
+    } else {
+        None
+    }
+}
+
+/// Picks the closest of some possible hits, given as distances and normals,
+/// ignoring any too close to the ray's origin.
+fn closest<I>(hits: I) -> Option<(f64, Vec3)>
+where
+    I: IntoIterator<Item = Option<(f64, Vec3)>>,
+{
+    hits.into_iter()
+        .flatten()
+        .filter(|&(t, _)| t > EPSILON)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+}
+
+/// Half the size of the bounding box of a circle, in each axis.
+fn circle_extent(normal: Vec3, radius: f64) -> Vec3 {
+    let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+    Vec3::new(extent(normal.x), extent(normal.y), extent(normal.z))
+}
+
+/// The point `turn` of the way around the circle of the given radius, facing
+/// `normal`.
+fn point_on_circle(centre: Vec3, normal: Vec3, radius: f64, turn: f64) -> Vec3 {
+    let phi = 2.0 * f64::consts::PI * turn;
+    let offset = Vec3::new(radius * phi.cos(), radius * phi.sin(), 0.0);
+    centre + brdf::orient(offset, normal)
+}
+
+impl<M: Material> Geometry for Disc<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (t, point) = intersect_plane(ray, self.centre, self.normal)?;
+        let offset = point - self.centre;
+        if offset.dot(offset) <= self.radius * self.radius {
+            Some(Hit {
+                t,
+                normal: self.normal,
+                material: &self.material,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let extent = circle_extent(self.normal, self.radius);
+        Some(BoundingBox::new(self.centre - extent, self.centre + extent))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let radius = self.radius * sample.x.sqrt();
+        let point = point_on_circle(self.centre, self.normal, radius, sample.y);
+        let area = f64::consts::PI * self.radius * self.radius;
+        Some((point, self.normal, 1.0 / area))
+    }
+}
+
+impl<M: Material> Geometry for Annulus<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (t, point) = intersect_plane(ray, self.centre, self.normal)?;
+        let offset = point - self.centre;
+        let distance_squared = offset.dot(offset);
+        let inner = self.inner_radius * self.inner_radius;
+        let outer = self.outer_radius * self.outer_radius;
+        if (inner..=outer).contains(&distance_squared) {
+            Some(Hit {
+                t,
+                normal: self.normal,
+                material: &self.material,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let extent = circle_extent(self.normal, self.outer_radius);
+        Some(BoundingBox::new(self.centre - extent, self.centre + extent))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let inner = self.inner_radius * self.inner_radius;
+        let outer = self.outer_radius * self.outer_radius;
+        let radius = (inner + sample.x * (outer - inner)).sqrt();
+        let point = point_on_circle(self.centre, self.normal, radius, sample.y);
+        let area = f64::consts::PI * (outer - inner);
+        Some((point, self.normal, 1.0 / area))
+    }
+}
+
+impl<M: Material> Geometry for Rectangle<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let normal = self.a.cross(self.b);
+        let (t, point) = intersect_plane(ray, self.corner, normal)?;
+
+        // the offset is `u * a + v * b`
+        let offset = point - self.corner;
+        let scale = normal.dot(normal);
+        let u = offset.cross(self.b).dot(normal) / scale;
+        let v = self.a.cross(offset).dot(normal) / scale;
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some(Hit {
+                t,
+                normal: normal.normalise(),
+                material: &self.material,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let bbox = BoundingBox::empty()
+            .include(self.corner)
+            .include(self.corner + self.a)
+            .include(self.corner + self.b);
+        Some(bbox.include(self.corner + self.a + self.b))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let point = self.corner + sample.x * self.a + sample.y * self.b;
+        let cross = self.a.cross(self.b);
+        Some((point, cross.normalise(), 1.0 / cross.mag()))
+    }
+}
+
+impl<M: Material> Geometry for Cylinder<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (o, d) = (ray.origin, ray.direction);
+        let r = self.radius;
+
+        let side = |t: f64| {
+            let point = ray.at(t);
+            if (self.bottom..=self.top).contains(&point.y) {
+                Some((t, Vec3::new(point.x / r, 0.0, point.z / r)))
+            } else {
+                None
+            }
+        };
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.z * d.z);
+        let c = o.x * o.x + o.z * o.z - r * r;
+        let sides = solve_quadratic(a, b, c).map(|(t_1, t_2)| [side(t_1), side(t_2)]);
+
+        let cap = |y: f64, normal: Vec3| {
+            let t = (y - o.y) / d.y;
+            let point = ray.at(t);
+            if self.open || point.x * point.x + point.z * point.z > r * r {
+                None
+            } else {
+                Some((t, normal))
+            }
+        };
+        let caps = [
+            cap(self.bottom, Vec3::new(0.0, -1.0, 0.0)),
+            cap(self.top, Vec3::new(0.0, 1.0, 0.0)),
+        ];
+
+        let (t, mut normal) = closest(sides.into_iter().flatten().chain(caps))?;
+        if self.open && normal.dot(d) > 0.0 {
+            normal = -normal;
+        }
+        Some(Hit {
+            t,
+            normal,
+            material: &self.material,
+        })
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let r = self.radius;
+        Some(BoundingBox::new(
+            Vec3::new(-r, self.bottom, -r),
+            Vec3::new(r, self.top, r),
+        ))
+    }
+
+    /// Chooses the side or one of the caps in proportion to their areas, then
+    /// a point on it.
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let r = self.radius;
+        let height = self.top - self.bottom;
+        let side = 2.0 * f64::consts::PI * r * height;
+        let cap = if self.open {
+            0.0
+        } else {
+            f64::consts::PI * r * r
+        };
+        let area = side + 2.0 * cap;
+
+        let phi = 2.0 * f64::consts::PI * sample.y;
+        let (cos, sin) = (phi.cos(), phi.sin());
+        let x = sample.x * area;
+        let (point, normal) = if x < side {
+            let y = self.bottom + height * x / side;
+            (Vec3::new(r * cos, y, r * sin), Vec3::new(cos, 0.0, sin))
+        } else {
+            // the part of the sample left over picks a cap, then the radius
+            let x = (x - side) / cap;
+            let (y, normal, x) = if x < 1.0 {
+                (self.bottom, -1.0, x)
+            } else {
+                (self.top, 1.0, x - 1.0)
+            };
+            let rho = r * x.min(1.0).sqrt();
+            (
+                Vec3::new(rho * cos, y, rho * sin),
+                Vec3::new(0.0, normal, 0.0),
+            )
+        };
+        Some((point, normal, 1.0 / area))
+    }
+}
+
+impl<M: Material> Geometry for Cone<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let (o, d) = (ray.origin, ray.direction);
+        let (r, h) = (self.radius, self.height);
+
+        // the side is where `x² + z² = k (h - y)²`, with `k = (r/h)²`
+        let k = (r / h) * (r / h);
+        let w = h - o.y;
+        let side = |t: f64| {
+            let point = ray.at(t);
+            if (0.0..=h).contains(&point.y) {
+                let normal = Vec3::new(point.x, k * (h - point.y), point.z);
+                Some((t, normal.normalise()))
+            } else {
+                None
+            }
+        };
+        let a = d.x * d.x + d.z * d.z - k * d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.z * d.z + k * w * d.y);
+        let c = o.x * o.x + o.z * o.z - k * w * w;
+        let sides = solve_quadratic(a, b, c).map(|(t_1, t_2)| [side(t_1), side(t_2)]);
+
+        let t = -o.y / d.y;
+        let point = ray.at(t);
+        let base = if self.open || point.x * point.x + point.z * point.z > r * r {
+            None
+        } else {
+            Some((t, Vec3::new(0.0, -1.0, 0.0)))
+        };
+
+        let (t, mut normal) = closest(sides.into_iter().flatten().chain([base]))?;
+        if self.open && normal.dot(d) > 0.0 {
+            normal = -normal;
+        }
+        Some(Hit {
+            t,
+            normal,
+            material: &self.material,
+        })
     }
 
     fn material(&self) -> &dyn Material {
         &self.material
     }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let r = self.radius;
+        Some(BoundingBox::new(
+            Vec3::new(-r, 0.0, -r),
+            Vec3::new(r, self.height, r),
+        ))
+    }
+
+    /// Chooses the side or the base in proportion to their areas, then a
+    /// point on it.
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let (r, h) = (self.radius, self.height);
+        let slant = (r * r + h * h).sqrt();
+        let side = f64::consts::PI * r * slant;
+        let base = if self.open {
+            0.0
+        } else {
+            f64::consts::PI * r * r
+        };
+        let area = side + base;
+
+        let phi = 2.0 * f64::consts::PI * sample.y;
+        let (cos, sin) = (phi.cos(), phi.sin());
+        let x = sample.x * area;
+        let (point, normal) = if x < side {
+            // the area grows with the square of the distance from the tip
+            let s = (x / side).sqrt();
+            let point = Vec3::new(r * s * cos, h * (1.0 - s), r * s * sin);
+            (point, Vec3::new(h * cos, r, h * sin) / slant)
+        } else {
+            let rho = r * ((x - side) / base).min(1.0).sqrt();
+            let point = Vec3::new(rho * cos, 0.0, rho * sin);
+            (point, Vec3::new(0.0, -1.0, 0.0))
+        };
+        Some((point, normal, 1.0 / area))
+    }
+}
+
+/// Finds the real roots of `ax² + bx + c = 0`, smallest first.
+///
+/// If `a` is zero the equation is linear, and its single root is returned
+/// twice. Returns `None` if there are no roots, or infinitely many.
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let x = -c / b;
+        return Some((x, x));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    // avoids cancellation between `b` and the square root
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x_1, x_2) = (q / a, c / q);
+    Some((x_1.min(x_2), x_1.max(x_2)))
+}
+
+/// Finds the largest real root of `x³ + ax² + bx + c = 0`.
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // substituting `x = y - a / 3` gives `y³ + py + q = 0`
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+
+    let discriminant = 0.25 * q * q + p * p * p / 27.0;
+    let y = if discriminant >= 0.0 {
+        // only one real root, by Cardano's formula
+        let s = discriminant.sqrt();
+        (-0.5 * q + s).cbrt() + (-0.5 * q - s).cbrt()
+    } else {
+        // three real roots, by the trigonometric method; `p` is negative
+        let rho = (-p / 3.0).sqrt();
+        let theta = (-0.5 * q / (rho * rho * rho)).clamp(-1.0, 1.0).acos();
+        2.0 * rho * (theta / 3.0).cos()
+    };
+    y - a / 3.0
+}
+
+/// Finds the real roots of `x⁴ + ax³ + bx² + cx + d = 0`, in no particular
+/// order.
+///
+/// This uses Ferrari's method, and then polishes the roots with a couple of
+/// steps of Newton's method to make up for the rounding errors it builds up.
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> [Option<f64>; 4] {
+    // substituting `x = y - a / 4` gives `y⁴ + py² + qy + r = 0`
+    let a2 = a * a;
+    let p = b - 0.375 * a2;
+    let q = c - 0.5 * a * b + 0.125 * a2 * a;
+    let r = d - 0.25 * a * c + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = [None; 4];
+    let mut push = |pair: Option<(f64, f64)>, i: usize| {
+        if let Some((y_1, y_2)) = pair {
+            roots[i] = Some(y_1);
+            roots[i + 1] = Some(y_2);
+        }
+    };
+
+    // adding `(y² + p/2)m + m²/4`, for the right `m`, to both sides of
+    // `(y² + p/2)² = p²/4 - qy - r` makes them both perfect squares
+    let m = largest_cubic_root(p, 0.25 * p * p - r, -0.125 * q * q);
+    // `m` is only zero when `q` is, but compare it with terms of the same
+    // size so that the test doesn't depend on the scale of the torus
+    let scale = p.abs().max(q.abs().powf(2.0 / 3.0));
+    if m > 1e-12 * scale {
+        let s = (2.0 * m).sqrt();
+        let k = q / (2.0 * s);
+        push(solve_quadratic(1.0, -s, 0.5 * p + m + k), 0);
+        push(solve_quadratic(1.0, s, 0.5 * p + m - k), 2);
+    } else if let Some((z_1, z_2)) = solve_quadratic(1.0, p, r) {
+        // `q` is zero, so this is a quadratic in `y²`
+        if z_1 >= 0.0 {
+            push(Some((-z_1.sqrt(), z_1.sqrt())), 0);
+        }
+        if z_2 >= 0.0 {
+            push(Some((-z_2.sqrt(), z_2.sqrt())), 2);
+        }
+    }
+
+    let polish = |mut x: f64| {
+        for _ in 0..2 {
+            let f = (((x + a) * x + b) * x + c) * x + d;
+            let df = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+            if df != 0.0 {
+                x -= f / df;
+            }
+        }
+        x
+    };
+    roots.map(|y| y.map(|y| polish(y - 0.25 * a)))
+}
+
+impl<M: Material> Geometry for Torus<M> {
+    /// Solves a quartic equation for the distance. The ray is first moved up
+    /// to the bounding box and normalised, which keeps the coefficients small
+    /// and the roots accurate.
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let bbox = self.bounding_box()?;
+        let (t_near, _) = bbox.intersect(ray, 1.0 / ray.direction, f64::INFINITY)?;
+        let length = ray.direction.mag();
+        let o = ray.at(t_near);
+        let d = ray.direction / length;
+
+        let swept = self.swept_radius * self.swept_radius;
+        let tube = self.tube_radius * self.tube_radius;
+        let f = o.dot(d);
+        let e = o.dot(o) + swept - tube;
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e - 4.0 * swept * (d.x * d.x + d.z * d.z),
+            4.0 * f * e - 8.0 * swept * (o.x * d.x + o.z * d.z),
+            e * e - 4.0 * swept * (o.x * o.x + o.z * o.z),
+        );
+
+        let s = roots
+            .iter()
+            .flatten()
+            .copied()
+            .filter(|&s| t_near + s / length > EPSILON)
+            .min_by(f64::total_cmp)?;
+
+        let p = o + s * d;
+        let k = p.dot(p) + swept - tube;
+        let l = k - 2.0 * swept;
+        Some(Hit {
+            t: t_near + s / length,
+            normal: Vec3::new(p.x * l, p.y * k, p.z * l).normalise(),
+            material: &self.material,
+        })
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (outer, r) = (self.swept_radius + self.tube_radius, self.tube_radius);
+        Some(BoundingBox::new(
+            Vec3::new(-outer, -r, -outer),
+            Vec3::new(outer, r, outer),
+        ))
+    }
+}
+
+/// Geometry placed in the world with a [`Transform`].
+///
+/// Rays are transformed into the object's own space to be intersected, so any
+/// object can be moved, turned, or stretched without changing it. An object
+/// shared using an [`Arc`] can be placed many times, like copies of a large
+/// mesh, while only being stored once.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::geometry::{Cuboid, Geometry, Instance};
+/// use raytracer::material::Matte;
+/// use raytracer::utility::{Colour, Ray, Transform, Vec3};
+///
+/// // a unit cube, turned to face diagonally
+/// let cube = Cuboid::new(
+///     Vec3::new(-0.5, -0.5, -0.5),
+///     Vec3::new(0.5, 0.5, 0.5),
+///     Matte::new(0.25, 0.65, Colour::white()),
+/// );
+/// let instance = Instance::new(cube, Transform::rotate_y(45.0));
+///
+/// // the edge now points straight at the ray
+/// let ray = Ray {
+///     origin: Vec3::new(0.0, 0.0, 5.0),
+///     direction: Vec3::new(0.0, 0.0, -1.0),
+/// };
+/// let hit = instance.hit(&ray).unwrap();
+/// assert!((hit.t - (5.0 - 0.5_f64.sqrt())).abs() < 1e-9);
+/// ```
+#[derive(Debug)]
+pub struct Instance<G: Geometry> {
+    /// The object, in its own space.
+    pub object: G,
+    /// Transforms from the object's space into the world.
+    pub transform: Transform,
+}
+
+impl<G: Geometry> Instance<G> {
+    pub fn new(object: G, transform: Transform) -> Self {
+        Self { object, transform }
+    }
+
+    /// Converts a hit on the object into the world's space. Distances along
+    /// the ray are unchanged, as the transformed ray isn't normalised.
+    fn to_world<'a>(&self, hit: Hit<'a>) -> Hit<'a> {
+        Hit {
+            normal: self.transform.transform_normal(hit.normal).normalise(),
+            ..hit
+        }
+    }
+}
+
+impl<G: Geometry> Geometry for Instance<G> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let local = self.transform.inverse().transform_ray(ray);
+        let hit = self.object.hit(&local)?;
+        Some(self.to_world(hit))
+    }
+
+    fn material(&self) -> &dyn Material {
+        self.object.material()
+    }
+
+    /// Encloses the transformed corners of the object's own bounding box. An
+    /// empty box stays empty.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let local = self.object.bounding_box()?;
+        if local.is_empty() {
+            return Some(local);
+        }
+        let BoundingBox { min, max } = local;
+        let bbox = (0..8)
+            .map(|corner| {
+                let pick = |axis: usize| {
+                    if corner & (1 << axis) == 0 {
+                        min[axis]
+                    } else {
+                        max[axis]
+                    }
+                };
+                Vec3::new(pick(0), pick(1), pick(2))
+            })
+            .fold(BoundingBox::empty(), |bbox, corner| {
+                bbox.include(self.transform.transform_point(corner))
+            });
+        Some(bbox)
+    }
+
+    /// Points are chosen as the object would choose them. Scaling changes the
+    /// area around each point, so the probability density is adjusted to
+    /// match.
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let (point, normal, pdf) = self.object.sample_surface(sample)?;
+        let normal = self.transform.transform_normal(normal);
+        let stretch = self.transform.determinant().abs() * normal.mag();
+        Some((
+            self.transform.transform_point(point),
+            normal.normalise(),
+            pdf / stretch,
+        ))
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let local = self.transform.inverse().transform_ray(ray);
+        let mut spans = self.object.spans(&local);
+        for span in &mut spans {
+            span.enter = span.enter.map(|hit| self.to_world(hit));
+            span.exit = span.exit.map(|hit| self.to_world(hit));
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Matte;
+    use crate::mesh::{Mesh, TriangleMesh};
+    use crate::utility::Colour;
+
+    fn matte() -> Matte {
+        Matte::new(0.25, 0.65, Colour::white())
+    }
+
+    /// A ray straight down onto the `z = 0` plane, through `(x, y)`.
+    fn ray_down(x: f64, y: f64) -> Ray {
+        Ray {
+            origin: Vec3::new(x, y, 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        }
+    }
+
+    #[test]
+    fn triangle_edges_and_vertices() {
+        let triangle = Triangle::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            matte(),
+        );
+
+        let hit = triangle.hit(&ray_down(0.25, 0.25)).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+
+        // points exactly on an edge or a vertex count as hits
+        for &(x, y) in &[(0.5, 0.0), (0.0, 0.5), (0.5, 0.5), (0.0, 0.0), (1.0, 0.0)] {
+            assert!(triangle.hit(&ray_down(x, y)).is_some(), "({}, {})", x, y);
+        }
+        // but anything outside doesn't
+        for &(x, y) in &[(-0.01, 0.5), (0.5, -0.01), (0.51, 0.51), (1.01, 0.0)] {
+            assert!(triangle.hit(&ray_down(x, y)).is_none(), "({}, {})", x, y);
+        }
+
+        // from behind, and parallel to the triangle
+        let behind = Ray {
+            origin: Vec3::new(0.25, 0.25, -1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        assert!(triangle.hit(&behind).is_none());
+        let parallel = Ray {
+            origin: Vec3::new(-1.0, 0.25, 0.0),
+            direction: Vec3::new(1.0, 0.0, 0.0),
+        };
+        assert!(triangle.hit(&parallel).is_none());
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).mag() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// A unit sphere at the origin, placed with `transform`.
+    fn unit_sphere(material: &Matte, transform: Transform) -> Instance<Sphere<Matte>> {
+        let sphere = Sphere {
+            centre: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: material.clone(),
+        };
+        Instance::new(sphere, transform)
+    }
+
+    #[test]
+    fn instance_hits() {
+        let material = matte();
+        let towards = |origin: Vec3, target: Vec3| Ray {
+            origin,
+            direction: (target - origin).normalise(),
+        };
+
+        // moved, it's hit where the sphere would be
+        let moved =
+            unit_sphere(&material, Transform::translate(Vec3::new(0.0, 2.0, 0.0)));
+        let ahead = Vec3::new(0.0, 2.0, 5.0);
+        let hit = moved
+            .hit(&towards(ahead, Vec3::new(0.0, 2.0, 0.0)))
+            .unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+        assert!(moved
+            .hit(&towards(origin, Vec3::new(0.0, 0.0, 0.0)))
+            .is_none());
+
+        // stretched along `x`, distances stay in world units
+        let stretched =
+            unit_sphere(&material, Transform::scale(Vec3::new(2.0, 1.0, 1.0)));
+        let origin = Vec3::new(5.0, 0.0, 0.0);
+        let hit = stretched
+            .hit(&towards(origin, Vec3::new(0.0, 0.0, 0.0)))
+            .unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        // off the axes the normal follows the ellipsoid, not the sphere
+        let point = Vec3::new(2.0 * 0.6, 0.8, 0.0);
+        let hit = stretched
+            .hit(&towards(Vec3::new(0.0, 5.0, 0.0), point))
+            .unwrap();
+        let expected = Vec3::new(point.x / 4.0, point.y, 0.0).normalise();
+        assert_close(hit.normal, expected);
+
+        // mirrored, normals still point out
+        let mirrored =
+            unit_sphere(&material, Transform::scale(Vec3::new(-1.0, 1.0, 1.0)));
+        let hit = mirrored
+            .hit(&towards(origin, Vec3::new(0.0, 0.0, 0.0)))
+            .unwrap();
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn instance_bounding_boxes() {
+        let material = matte();
+        let transform = Transform::scale(Vec3::new(2.0, 1.0, 1.0))
+            .then(Transform::rotate_z(90.0))
+            .then(Transform::translate(Vec3::new(0.0, 0.0, 3.0)));
+        let bbox = unit_sphere(&material, transform).bounding_box().unwrap();
+        assert_close(bbox.min, Vec3::new(-1.0, -2.0, 2.0));
+        assert_close(bbox.max, Vec3::new(1.0, 2.0, 4.0));
+
+        // an empty box doesn't become full of NaNs
+        let empty = TriangleMesh::new(Mesh::default(), material);
+        let instance = Instance::new(empty, transform);
+        assert!(instance.bounding_box().unwrap().is_empty());
+    }
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction }
+    }
+
+    #[test]
+    fn cone_hits() {
+        let cone = Cone::new(1.0, 2.0, matte());
+        let slant = Vec3::new(2.0, 1.0, 0.0).normalise();
+
+        // along each axis
+        let hit = cone.hit(&ray(Vec3::new(5.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert_close(hit.normal, slant);
+
+        let hit = cone.hit(&ray(Vec3::new(0.5, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        assert_close(hit.normal, slant);
+
+        let hit = cone.hit(&ray(Vec3::new(0.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, -1.0, 0.0));
+
+        // parallel to the far side, so the side's equation is linear
+        let hit = cone.hit(&ray(Vec3::new(0.75, 3.0, 0.0), Vec3::new(-1.0, -2.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 0.625).abs() < 1e-9);
+        assert_close(hit.normal, slant);
+
+        // grazing the side, just inside and just outside
+        let graze =
+            |x: f64| cone.hit(&ray(Vec3::new(x, 1.0, 5.0), Vec3::new(0.0, 0.0, -1.0)));
+        let hit = graze(0.5 - 1e-6).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-2);
+        assert!(hit.normal.dot(slant) > 0.99);
+        assert!(graze(0.5 + 1e-6).is_none());
+    }
+
+    #[test]
+    fn torus_hits() {
+        let torus = Torus::new(2.0, 0.5, matte());
+
+        // along each axis, from outside and from the hole
+        let hit = torus.hit(&ray(Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(1.0, 0.0, 0.0));
+
+        let hit = torus.hit(&ray(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, 0.0, -1.0));
+
+        let hit = torus.hit(&ray(Vec3::new(-2.0, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0)));
+        let hit = hit.unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+        assert_close(hit.normal, Vec3::new(0.0, -1.0, 0.0));
+
+        // grazing the top of the tube, and the outside of the ring
+        let over =
+            |y: f64| torus.hit(&ray(Vec3::new(5.0, y, 0.0), Vec3::new(-1.0, 0.0, 0.0)));
+        let hit = over(0.5 - 1e-6).unwrap();
+        assert!((hit.t - 3.0).abs() < 1e-2);
+        assert!(hit.normal.y > 0.99);
+        assert!(over(0.5 + 1e-6).is_none());
+
+        let past =
+            |x: f64| torus.hit(&ray(Vec3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0)));
+        let hit = past(2.5 - 1e-6).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-2);
+        assert!(hit.normal.x > 0.99);
+        assert!(past(2.5 + 1e-6).is_none());
+
+        // the same shape at a very different scale
+        let tiny = Torus::new(2e-6, 5e-7, matte());
+        let hit = tiny.hit(&ray(Vec3::new(5.0, 2e-7, 0.0), Vec3::new(-1.0, 0.0, 0.0)));
+        let hit = hit.unwrap();
+        let x = 2e-6 + (0.25e-12_f64 - 0.04e-12).sqrt();
+        assert!((hit.t - (5.0 - x)).abs() < 1e-12);
+        assert_close(hit.normal, Vec3::new(0.84_f64.sqrt(), 0.4, 0.0));
+    }
 }
@@ -1,9 +1,18 @@
 pub mod brdf;
+pub mod btdf;
+pub mod bvh;
 pub mod camera;
+pub mod csg;
+pub mod environment;
+pub mod film;
 pub mod geometry;
 pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod obj;
 pub mod sampler;
+pub mod scene;
+pub mod tonemap;
 pub mod tracer;
 pub mod utility;
 pub mod world;
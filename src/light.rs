@@ -1,14 +1,97 @@
 //! Emitters and ambient lights.
+//!
+//! Lights are sampled to find the light arriving at an intersection: lights
+//! which emit from a single point or direction always give the same sample,
+//! but [area lights](AreaLight) choose a different point on their surface each
+//! time, giving soft shadows once many samples are averaged.
 
-use crate::utility::{Colour, Ray, Vec3};
+use crate::brdf;
+use crate::environment::EnvironmentMap;
+use crate::geometry::{Geometry, EPSILON};
+use crate::sampler::{Generator, Samples};
+use crate::utility::{Colour, Ray, Vec2, Vec3};
 use crate::world::{Intersection, World};
 
+use std::f64::consts;
 use std::fmt::Debug;
+use std::sync::Arc;
 
-pub trait Light: Debug {
-    fn direction(&self, hit: &Intersection) -> Vec3;
-    fn radiance(&self, hit: &Intersection) -> Colour;
-    fn in_shadow(&self, ray: Ray, world: &World) -> bool;
+pub trait Light: Debug + Send + Sync {
+    /// Chooses a point on the light which illuminates `hit`.
+    fn sample(&self, hit: &Intersection) -> LightSample;
+
+    /// Whether anything blocks the light from `sample` before it reaches the
+    /// origin of the shadow `ray`, which points towards the light.
+    fn in_shadow(&self, ray: Ray, sample: &LightSample, world: &World) -> bool;
+}
+
+/// Light arriving at an intersection from a point on a light.
+///
+/// The light reflected from the intersection is estimated by
+/// `f * radiance * cos θ * geometry / pdf`, where `f` is the BRDF and `θ` is
+/// the angle between the normal and `direction`.
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// The point on the light. For lights infinitely far away this is just
+    /// some point in the light's direction.
+    pub point: Vec3,
+    /// The normalised direction from the intersection towards the light.
+    pub direction: Vec3,
+    /// The radiance emitted from the point towards the intersection.
+    pub radiance: Colour,
+    /// How much of the light's area is seen from the intersection: the cosine
+    /// of the angle at the light over the squared distance. This is `1` for
+    /// lights which have no area.
+    pub geometry: f64,
+    /// The probability density of choosing the point.
+    ///
+    /// For lights with an area this is by area on the light, which `geometry`
+    /// converts to solid angle. When `geometry` is `1` it's by solid angle
+    /// around the intersection instead, as for an [`EnvironmentLight`], or
+    /// just `1` for lights from a single point or direction.
+    pub pdf: f64,
+}
+
+impl LightSample {
+    /// Creates a sample for a light emitting from a single point, or from
+    /// infinitely far away in a single direction.
+    pub fn delta(point: Vec3, direction: Vec3, radiance: Colour) -> Self {
+        Self {
+            point,
+            direction,
+            radiance,
+            geometry: 1.0,
+            pdf: 1.0,
+        }
+    }
+
+    /// Creates a sample for a point on the surface of a light, which emits
+    /// `radiance` from the side its `normal` faces.
+    fn surface(
+        hit: &Intersection,
+        point: Vec3,
+        normal: Vec3,
+        radiance: Colour,
+        pdf: f64,
+    ) -> Self {
+        let offset = point - hit.hit_point;
+        let distance_squared = offset.dot(offset);
+        let direction = offset / distance_squared.sqrt();
+
+        let cos_light = -normal.dot(direction);
+        let (radiance, geometry) = if cos_light > 0.0 {
+            (radiance, cos_light / distance_squared)
+        } else {
+            (Colour::black(), 0.0)
+        };
+        Self {
+            point,
+            direction,
+            radiance,
+            geometry,
+            pdf,
+        }
+    }
 }
 
 /// Ambient lighting to give a base diffuse shading.
@@ -31,29 +114,116 @@ impl Ambient {
     }
 }
 
+/// Ambient light arrives evenly from every direction, so the sample's
+/// direction is just the normal.
 impl Light for Ambient {
-    fn direction(&self, _hit: &Intersection) -> Vec3 {
-        // unused, in theory
-        Vec3::new(0.0, 0.0, 0.0)
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        LightSample::delta(hit.hit_point, hit.normal, self.scale * self.colour)
     }
 
-    fn radiance(&self, _hit: &Intersection) -> Colour {
-        self.scale * self.colour
+    fn in_shadow(&self, _ray: Ray, _sample: &LightSample, _world: &World) -> bool {
+        false
     }
+}
 
-    fn in_shadow(&self, _ray: Ray, _world: &World) -> bool {
-        false
+/// Ambient lighting which is blocked by nearby objects.
+///
+/// Rather than lighting everything evenly like [`Ambient`], this casts a ray
+/// in a cosine-weighted direction over the hemisphere around the normal,
+/// chosen by a sampler. If it hits anything within `max_distance`, no ambient light
+/// arrives. Creases and corners are hit more often, so they're darker once
+/// many samples are averaged, which grounds objects in the world.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::light::AmbientOccluder;
+/// use raytracer::sampler::MultiJittered;
+///
+/// // only objects closer than 5 units shade each other
+/// let ambient = AmbientOccluder::new(1.0, 5.0, &MultiJittered::new(64));
+/// ```
+#[derive(Debug)]
+pub struct AmbientOccluder {
+    pub scale: f64,
+    pub colour: Colour,
+    /// How far away objects can be and still block the light. This can be
+    /// infinite, for objects to be shaded by everything around them.
+    pub max_distance: f64,
+    samples: Samples<Vec3>,
+}
+
+impl AmbientOccluder {
+    /// Creates a new ambient occluder, using `sampler` to choose directions.
+    pub fn new<G: Generator + ?Sized>(
+        scale: f64,
+        max_distance: f64,
+        sampler: &G,
+    ) -> Self {
+        Self::with_colour(scale, Colour::white(), max_distance, sampler)
+    }
+
+    pub fn with_colour<G: Generator + ?Sized>(
+        scale: f64,
+        colour: Colour,
+        max_distance: f64,
+        sampler: &G,
+    ) -> Self {
+        Self {
+            scale,
+            colour,
+            max_distance,
+            samples: sampler.gen_hemisphere_samples(1.0),
+        }
+    }
+}
+
+/// Materials never test ambient light for shadows, so the occlusion test is
+/// already included in the sample: the radiance is black if it's blocked.
+impl Light for AmbientOccluder {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let direction = brdf::orient(self.samples.sample(hit.depth), hit.normal);
+        let sample = LightSample::delta(
+            hit.hit_point + direction,
+            direction,
+            self.scale * self.colour,
+        );
+        let ray = Ray {
+            origin: hit.hit_point,
+            direction,
+        };
+        if self.in_shadow(ray, &sample, hit.world) {
+            LightSample {
+                radiance: Colour::black(),
+                ..sample
+            }
+        } else {
+            sample
+        }
+    }
+
+    fn in_shadow(&self, ray: Ray, _sample: &LightSample, world: &World) -> bool {
+        world.objects.hit_any(&ray, self.max_distance)
     }
 }
 
+/// The falloff exponent for physically correct point lights.
+pub const INVERSE_SQUARE: f64 = 2.0;
+
 /// A light emitting from an infinitely small point.
 ///
-/// This implementation has no distance attenuation.
+/// The radiance falls off with distance `d` from the light as `1 / d^falloff`.
+/// By default this is the physically correct inverse-square law, so `scale` is
+/// the radiance at a distance of `1`.
 #[derive(Debug)]
 pub struct PointLight {
     pub scale: f64,
     pub colour: Colour,
     pub location: Vec3,
+    /// The exponent of the distance attenuation. [`INVERSE_SQUARE`] is
+    /// physically correct, but smaller values can be used to light larger
+    /// areas more evenly, and `0` disables attenuation entirely.
+    pub falloff: f64,
 }
 
 impl PointLight {
@@ -66,27 +236,451 @@ impl PointLight {
             scale,
             location,
             colour,
+            falloff: INVERSE_SQUARE,
         }
     }
 }
 
 impl Light for PointLight {
-    fn direction(&self, hit: &Intersection) -> Vec3 {
-        (self.location - hit.hit_point).normalise()
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let offset = self.location - hit.hit_point;
+        let distance = offset.mag();
+        let radiance = self.scale * self.colour / distance.powf(self.falloff);
+        LightSample::delta(self.location, offset / distance, radiance)
     }
 
-    fn radiance(&self, _hit: &Intersection) -> Colour {
-        // no distance attenuation, so basically just ambient
-        self.scale * self.colour
+    fn in_shadow(&self, ray: Ray, _sample: &LightSample, world: &World) -> bool {
+        let distance = (self.location - ray.origin).mag();
+        world.objects.hit_any(&ray, distance)
     }
+}
 
-    fn in_shadow(&self, ray: Ray, world: &World) -> bool {
-        let offset = self.location - ray.origin;
-        let distance_squared = offset.dot(offset);
+/// A light infinitely far away, shining in a single direction everywhere.
+///
+/// This is a good approximation for the sun. There is no attenuation, and
+/// anything at all in the way of the light casts a shadow.
+#[derive(Debug)]
+pub struct Directional {
+    pub scale: f64,
+    pub colour: Colour,
+    /// The direction the light travels in, which must be normalised.
+    pub direction: Vec3,
+}
+
+impl Directional {
+    pub fn new(scale: f64, direction: Vec3) -> Self {
+        Self::with_colour(scale, direction, Colour::white())
+    }
+
+    pub fn with_colour(scale: f64, direction: Vec3, colour: Colour) -> Self {
+        Self {
+            scale,
+            colour,
+            direction: direction.normalise(),
+        }
+    }
+}
+
+impl Light for Directional {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let point = hit.hit_point - self.direction;
+        LightSample::delta(point, -self.direction, self.scale * self.colour)
+    }
+
+    fn in_shadow(&self, ray: Ray, _sample: &LightSample, world: &World) -> bool {
+        world.objects.hit_any(&ray, f64::INFINITY)
+    }
+}
+
+/// A point light which only shines within a cone, like a stage light.
+///
+/// The light is at full strength within the `inner` angle of its axis, fading
+/// smoothly to nothing at the `outer` angle. It's attenuated with distance in
+/// the same way as a [`PointLight`].
+#[derive(Debug)]
+pub struct SpotLight {
+    pub scale: f64,
+    pub colour: Colour,
+    pub location: Vec3,
+    /// The direction the light points in, which must be normalised.
+    pub direction: Vec3,
+    /// The angle from the axis within which the light is at full strength, in
+    /// degrees.
+    pub inner: f64,
+    /// The angle from the axis beyond which there is no light, in degrees.
+    pub outer: f64,
+    /// The exponent of the distance attenuation; see [`PointLight::falloff`].
+    pub falloff: f64,
+}
+
+impl SpotLight {
+    pub fn new(
+        scale: f64,
+        location: Vec3,
+        direction: Vec3,
+        inner: f64,
+        outer: f64,
+    ) -> Self {
+        Self::with_colour(scale, location, direction, inner, outer, Colour::white())
+    }
+
+    pub fn with_colour(
+        scale: f64,
+        location: Vec3,
+        direction: Vec3,
+        inner: f64,
+        outer: f64,
+        colour: Colour,
+    ) -> Self {
+        Self {
+            scale,
+            colour,
+            location,
+            direction: direction.normalise(),
+            inner,
+            outer,
+            falloff: INVERSE_SQUARE,
+        }
+    }
+
+    /// How much of the light reaches `point`, from `0` outside the cone to `1`
+    /// inside the inner cone.
+    fn cone(&self, point: Vec3) -> f64 {
+        let cos_angle = self.direction.dot((point - self.location).normalise());
+        let cos_inner = self.inner.to_radians().cos();
+        let cos_outer = self.outer.to_radians().cos();
+        if cos_angle >= cos_inner {
+            1.0
+        } else if cos_angle <= cos_outer {
+            0.0
+        } else {
+            // smoothstep, so there's no visible edge at either angle
+            let t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let offset = self.location - hit.hit_point;
+        let distance = offset.mag();
+        let cone = self.cone(hit.hit_point);
+        let radiance = self.scale * cone * self.colour / distance.powf(self.falloff);
+        LightSample::delta(self.location, offset / distance, radiance)
+    }
+
+    fn in_shadow(&self, ray: Ray, _sample: &LightSample, world: &World) -> bool {
+        let distance = (self.location - ray.origin).mag();
+        world.objects.hit_any(&ray, distance)
+    }
+}
+
+/// The shape of an [`AreaLight`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// A parallelogram with one corner at `corner`, and sides `a` and `b`. It
+    /// emits light from the side which `a × b` faces.
+    Rectangle { corner: Vec3, a: Vec3, b: Vec3 },
+    /// A disc, emitting light from the side which `normal` faces.
+    Disc {
+        centre: Vec3,
+        normal: Vec3,
+        radius: f64,
+    },
+    /// A sphere, emitting light outwards.
+    Sphere { centre: Vec3, radius: f64 },
+}
+
+impl Shape {
+    /// The surface area of the shape.
+    pub fn area(&self) -> f64 {
+        match *self {
+            Shape::Rectangle { a, b, .. } => a.cross(b).mag(),
+            Shape::Disc { radius, .. } => consts::PI * radius * radius,
+            Shape::Sphere { radius, .. } => 4.0 * consts::PI * radius * radius,
+        }
+    }
+
+    /// Maps a sample to a point on the shape, returning the point and the
+    /// shape's normal there.
+    ///
+    /// Discs take samples on the unit disc, and other shapes take samples on
+    /// the unit square.
+    fn point(&self, sample: Vec2) -> (Vec3, Vec3) {
+        match *self {
+            Shape::Rectangle { corner, a, b } => {
+                let point = corner + sample.x * a + sample.y * b;
+                (point, a.cross(b).normalise())
+            },
+            Shape::Disc {
+                centre,
+                normal,
+                radius,
+            } => {
+                // jitter "up" so it's never parallel to the normal
+                let u = Vec3::new(0.0034, 1.0, 0.0071).cross(normal).normalise();
+                let v = normal.cross(u);
+                let point = centre + radius * (sample.x * u + sample.y * v);
+                (point, normal)
+            },
+            Shape::Sphere { centre, radius } => {
+                let z = 1.0 - 2.0 * sample.x;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * consts::PI * sample.y;
+                let normal = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+                (centre + radius * normal, normal)
+            },
+        }
+    }
+}
+
+/// A light emitting evenly from every point on the surface of a [`Shape`].
+///
+/// Points are chosen uniformly over the surface, so shadows have soft edges
+/// once enough samples are taken per pixel. The light itself isn't visible to
+/// the camera.
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+    pub shape: Shape,
+    pub scale: f64,
+    pub colour: Colour,
+    samples: Samples<Vec2>,
+}
+
+impl AreaLight {
+    /// Creates a new area light, using `sampler` to choose points on its
+    /// surface.
+    ///
+    /// A disc's normal must be normalised.
+    pub fn new<G: Generator + ?Sized>(
+        shape: Shape,
+        scale: f64,
+        colour: Colour,
+        sampler: &G,
+    ) -> Self {
+        let samples = match shape {
+            Shape::Disc { .. } => sampler.gen_disc_samples(),
+            _ => sampler.gen_square_samples(),
+        };
+        Self {
+            shape,
+            scale,
+            colour,
+            samples,
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let (point, normal) = self.shape.point(self.samples.sample(hit.depth));
+        let pdf = 1.0 / self.shape.area();
+        LightSample::surface(hit, point, normal, self.scale * self.colour, pdf)
+    }
+
+    fn in_shadow(&self, ray: Ray, sample: &LightSample, world: &World) -> bool {
+        let distance = (sample.point - ray.origin).mag();
+        world.objects.hit_any(&ray, distance)
+    }
+}
+
+/// Turns an object in the world into a light, using the radiance emitted by
+/// its [material](crate::material::Material::emission).
+///
+/// Unlike an [`AreaLight`] the object is visible to the camera, as long as
+/// it's also one of the world's objects. Points are chosen uniformly over its
+/// surface.
+#[derive(Debug)]
+pub struct GeometryLight {
+    pub object: Arc<dyn Geometry>,
+    samples: Samples<Vec2>,
+}
+
+impl GeometryLight {
+    /// Creates a new light from an object, using `sampler` to choose points on
+    /// its surface.
+    ///
+    /// Returns `None` if the object can't be
+    /// [sampled](crate::geometry::Geometry::sample_surface), like a plane.
+    pub fn new<G: Generator + ?Sized>(
+        object: Arc<dyn Geometry>,
+        sampler: &G,
+    ) -> Option<Self> {
+        object.sample_surface(Vec2::new(0.5, 0.5))?;
+        let samples = sampler.gen_square_samples();
+        Some(Self { object, samples })
+    }
+}
+
+impl Light for GeometryLight {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let (point, normal, pdf) = self
+            .object
+            .sample_surface(self.samples.sample(hit.depth))
+            .expect("checked when created");
+        let radiance = self.object.material().emission();
+        LightSample::surface(hit, point, normal, radiance, pdf)
+    }
+
+    /// The object itself is in the world, so the shadow ray stops just short
+    /// of it.
+    fn in_shadow(&self, ray: Ray, sample: &LightSample, world: &World) -> bool {
+        let distance = (sample.point - ray.origin).mag();
+        world.objects.hit_any(&ray, distance - EPSILON)
+    }
+}
+
+/// Light arriving from an [environment map](EnvironmentMap) surrounding the
+/// world.
+///
+/// Directions are chosen in proportion to the brightness of the map, so small,
+/// bright features like the sun cast sharp shadows without many samples. The
+/// map should also be the world's [environment](crate::world::World), so that
+/// it's visible.
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    pub map: Arc<EnvironmentMap>,
+    samples: Samples<Vec2>,
+}
+
+impl EnvironmentLight {
+    /// Creates a new environment light, using `sampler` to choose directions.
+    pub fn new<G: Generator + ?Sized>(map: Arc<EnvironmentMap>, sampler: &G) -> Self {
+        let samples = sampler.gen_square_samples();
+        Self { map, samples }
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, hit: &Intersection) -> LightSample {
+        let sample = self.samples.sample(hit.depth);
+        let (direction, pdf) = self.map.sample_direction(sample);
+        LightSample {
+            point: hit.hit_point + direction,
+            direction,
+            radiance: self.map.radiance(direction),
+            geometry: 1.0,
+            pdf,
+        }
+    }
+
+    fn in_shadow(&self, ray: Ray, _sample: &LightSample, world: &World) -> bool {
+        world.objects.hit_any(&ray, f64::INFINITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::material::Emissive;
+    use crate::sampler::Jittered;
+    use crate::tracer::RayCaster;
+    use crate::world::ViewPlane;
+
+    /// A world with nothing in it, to light hits with.
+    fn empty_world() -> World {
+        World {
+            background: Colour::black(),
+            environment: None,
+            view: ViewPlane::new(1, 1, 1.0, Jittered::new(1)),
+            objects: Bvh::new(Vec::new()),
+            ambient: Box::new(Ambient::new(0.0)),
+            lights: Vec::new(),
+        }
+    }
+
+    /// Samples `light` for a hit at `point` on a surface facing up.
+    fn sample_at(light: &dyn Light, point: Vec3) -> LightSample {
+        let world = empty_world();
+        let material = Emissive::new(0.0, Colour::black());
+        let ray = Ray {
+            origin: point + Vec3::new(0.0, 1.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+        };
+        let hit = Intersection {
+            ray,
+            t: 1.0,
+            hit_point: point,
+            normal: Vec3::new(0.0, 1.0, 0.0),
+            depth: 0,
+            material: &material,
+            world: &world,
+            tracer: &RayCaster {},
+        };
+        light.sample(&hit)
+    }
+
+    fn assert_close(actual: Colour, expected: f64) {
+        for channel in &[actual.r, actual.g, actual.b] {
+            assert!(
+                (channel - expected).abs() < 1e-9,
+                "{:?} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn point_light_falloff() {
+        let mut light = PointLight::new(10.0, Vec3::new(0.0, 5.0, 0.0));
+        let sample = sample_at(&light, Vec3::new(0.0, 0.0, 0.0));
+        assert_close(sample.radiance, 10.0 / 25.0);
+        assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!((sample.geometry, sample.pdf), (1.0, 1.0));
+
+        // twice as far away gets a quarter of the light
+        let sample = sample_at(&light, Vec3::new(0.0, -5.0, 0.0));
+        assert_close(sample.radiance, 10.0 / 100.0);
+
+        light.falloff = 1.0;
+        assert_close(sample_at(&light, Vec3::new(0.0, -5.0, 0.0)).radiance, 1.0);
+        light.falloff = 0.0;
+        assert_close(sample_at(&light, Vec3::new(0.0, -5.0, 0.0)).radiance, 10.0);
+    }
+
+    #[test]
+    fn directional_light_is_constant() {
+        let light = Directional::new(2.0, Vec3::new(1.0, -1.0, 0.0));
+        for &point in &[Vec3::new(0.0, 0.0, 0.0), Vec3::new(100.0, -50.0, 3.0)] {
+            let sample = sample_at(&light, point);
+            assert_close(sample.radiance, 2.0);
+            let expected = Vec3::new(-1.0, 1.0, 0.0).normalise();
+            assert!((sample.direction - expected).mag() < 1e-12);
+        }
+    }
+
+    /// Spot lights are at full strength inside the inner cone, fade smoothly
+    /// between the cones, and are dark outside the outer cone.
+    #[test]
+    fn spot_light_cone() {
+        let down = Vec3::new(0.0, -1.0, 0.0);
+        let mut light = SpotLight::new(4.0, Vec3::new(0.0, 1.0, 0.0), down, 20.0, 40.0);
+        light.falloff = 0.0;
+
+        // a point on the floor at `degrees` from the light's axis
+        let at = |degrees: f64| Vec3::new(degrees.to_radians().tan(), 0.0, 0.0);
+
+        assert_close(sample_at(&light, at(0.0)).radiance, 4.0);
+        assert_close(sample_at(&light, at(19.9)).radiance, 4.0);
+        assert_close(sample_at(&light, at(40.1)).radiance, 0.0);
+        assert_close(sample_at(&light, at(80.0)).radiance, 0.0);
+
+        let mut previous = 4.0;
+        for degrees in 21..40 {
+            let radiance = sample_at(&light, at(f64::from(degrees))).radiance.r;
+            assert!(radiance < previous && radiance > 0.0, "{}", degrees);
+            previous = radiance;
+        }
+
+        // behind the light
+        let behind = sample_at(&light, Vec3::new(0.0, 2.0, 0.0)).radiance;
+        assert_close(behind, 0.0);
 
-        world.objects.iter().any(|obj| match obj.hit(&ray) {
-            Some((t, _)) => t * t < distance_squared,
-            _ => false,
-        })
+        // attenuated with distance like a point light
+        light.falloff = INVERSE_SQUARE;
+        assert_close(sample_at(&light, Vec3::new(0.0, -1.0, 0.0)).radiance, 1.0);
     }
 }
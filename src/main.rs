@@ -1,141 +1,248 @@
-#![allow(dead_code)]
-
-use raytracer::{
-    camera::{self, Camera},
-    geometry::{self, Geometry},
-    light::{self, Light},
-    material::{Matte, Phong},
-    sampler::{self, Default as Sampler},
-    tracer::RayCaster,
-    utility::{Colour, Vec3},
-    world::{ViewPlane, World},
+use raytracer::camera::RenderOptions;
+use raytracer::sampler;
+use raytracer::scene::{Overrides, Scene};
+use raytracer::tonemap::ToneMap;
+use raytracer::tracer::{
+    AmbientOcclusion, PathTracer, RayCaster, SimpleTracer, Tracer, Whitted,
 };
 
-use std::env;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::time::Instant;
 
-fn main() {
-    let filename = env::args().nth(1).unwrap_or("demo.png".to_string());
+/// Renders a scene described in a TOML file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// The scene file to render.
+    scene: PathBuf,
+
+    /// Where to save the rendered image [default: the scene's name, as a PNG]
+    ///
+    /// The format is chosen by the file extension. OpenEXR (.exr), Radiance
+    /// (.hdr), and portable float maps (.pfm) keep the full, linear range of
+    /// colours; anything else is saved as an 8-bit image.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Image size to use instead of the scene's, as WIDTHxHEIGHT.
+    #[arg(short, long, value_name = "WIDTHxHEIGHT", value_parser = parse_resolution)]
+    resolution: Option<(u32, u32)>,
+
+    /// Number of samples per pixel to use instead of the scene's.
+    #[arg(short, long)]
+    samples: Option<usize>,
+
+    /// The tracer used to colour each ray.
+    #[arg(short, long, value_enum, default_value_t = TracerKind::RayCaster)]
+    tracer: TracerKind,
+
+    /// Maximum number of bounces for recursive tracers.
+    #[arg(long, default_value_t = 5)]
+    max_depth: u32,
+
+    /// Number of bounces before paths may be randomly terminated, for the
+    /// path tracer.
+    #[arg(long, default_value_t = 3)]
+    roulette_depth: u32,
+
+    /// How far away objects can be and still occlude, for the ambient
+    /// occlusion tracer.
+    #[arg(long, default_value_t = f64::INFINITY, value_parser = parse_positive)]
+    occlusion_distance: f64,
+
+    /// Tone mapping operator to use instead of the scene's, for 8-bit images.
+    #[arg(long, value_enum, requires_if("reinhard-extended", "white"))]
+    tone_map: Option<ToneMapKind>,
+
+    /// Luminance mapped to pure white by the `reinhard-extended` operator.
+    #[arg(long, value_parser = parse_positive)]
+    white: Option<f64>,
+
+    /// Exposure adjustment in stops to use instead of the scene's.
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Name of a camera from the scene's `[cameras]` table to render with.
+    #[arg(short, long)]
+    camera: Option<String>,
+
+    /// Number of threads to render with [default: the number of CPUs]
+    #[arg(short = 'j', long)]
+    threads: Option<NonZeroUsize>,
+
+    /// Seed for random sampling, to make renders reproducible.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Don't show progress or timing information.
+    #[arg(short, long)]
+    quiet: bool,
+}
 
-    let now = Instant::now();
+/// The tracers which can be chosen from the command line.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TracerKind {
+    /// Shades the closest object hit by each ray.
+    RayCaster,
+    /// Colours pixels red where any object is hit.
+    Simple,
+    /// Recursively follows reflected and transmitted rays.
+    Whitted,
+    /// Follows random paths for full global illumination.
+    Path,
+    /// Shows only ambient occlusion, like an object made of clay.
+    Occlusion,
+}
 
-    let (world, camera) = build_scene();
-    let scene = camera.render_scene(&world, RayCaster {});
+impl TracerKind {
+    /// Creates the tracer, sampling from `sampler` where it needs to.
+    fn tracer<G: sampler::Generator + ?Sized>(
+        self,
+        args: &Args,
+        sampler: &G,
+    ) -> Box<dyn Tracer> {
+        match self {
+            TracerKind::RayCaster => Box::new(RayCaster {}),
+            TracerKind::Simple => Box::new(SimpleTracer {}),
+            TracerKind::Whitted => Box::new(Whitted::new(args.max_depth)),
+            TracerKind::Path => {
+                Box::new(PathTracer::new(args.max_depth, args.roulette_depth))
+            },
+            TracerKind::Occlusion => {
+                Box::new(AmbientOcclusion::new(args.occlusion_distance, sampler))
+            },
+        }
+    }
+}
 
-    let elapsed = now.elapsed().as_millis();
-    println!("Rendered in {} seconds.", elapsed as f64 / 1000.0);
+/// The tone mapping operators which can be chosen from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum ToneMapKind {
+    /// Clips anything too bright.
+    Clamp,
+    /// Reinhard's operator.
+    Reinhard,
+    /// Reinhard's operator, with a given white point.
+    ReinhardExtended,
+    /// John Hable's filmic curve.
+    Hable,
+    /// An approximation of the ACES filmic curve.
+    Aces,
+}
 
-    match scene.save(&filename) {
-        Ok(_) => println!("Saved to \"{}\".", filename),
-        Err(_) => println!("Failed to write to \"{}\".", filename),
+impl ToneMapKind {
+    fn tone_map(self, white: Option<f64>) -> ToneMap {
+        match self {
+            ToneMapKind::Clamp => ToneMap::Clamp,
+            ToneMapKind::Reinhard => ToneMap::Reinhard,
+            ToneMapKind::ReinhardExtended => ToneMap::ReinhardExtended {
+                white: white.expect("required by clap"),
+            },
+            ToneMapKind::Hable => ToneMap::Hable,
+            ToneMapKind::Aces => ToneMap::Aces,
+        }
     }
 }
 
-fn build_scene_2() -> (World, impl Camera) {
-    let sampler = Sampler::new(16);
-    let view = ViewPlane::new(400, 400, 1.0, sampler);
+/// Parses a number which must be greater than zero.
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(x) if x > 0.0 => Ok(x),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(err) => Err(format!("invalid number `{}`: {}", s, err)),
+    }
+}
 
-    let location = camera::Location {
-        eye: Vec3::new(0.0, 0.0, 500.0),
-        centre: Vec3::new(-5.0, 0.0, 0.0),
-        up: Vec3::new(0.0, 1.0, 0.0),
-    };
-    let view_len = 850.0;
-    let camera = camera::Pinhole::new(location, view_len, 2.0);
-
-    let ambient = Box::new(light::Ambient::new(1.0));
-    let mut lights: Vec<Box<dyn Light>> = Vec::new();
-    lights.push(Box::new(light::PointLight::new(
-        3.0,
-        Vec3::new(100.0, 50.0, 150.0),
-    )));
-
-    let mut objects: Vec<Box<dyn Geometry>> = Vec::new();
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(10.0, -5.0, 0.0),
-        radius: 27.0,
-        material: Phong::new(0.25, 0.65, 0.2, 15.0, Colour::new(1.0, 1.0, 0.0)),
-    }));
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(-20.0, 10.0, -50.0),
-        radius: 27.0,
-        material: Matte::new(0.25, 0.65, Colour::new(1.0, 0.5, 0.0)),
-    }));
-
-    let world = World {
-        background: Colour::black(),
-        view,
-        objects,
-        ambient,
-        lights,
+/// Parses a resolution like `800x600`.
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| "expected WIDTHxHEIGHT, like 800x600".to_string())?;
+    let parse = |n: &str| match n.trim().parse() {
+        Ok(0) => Err("image size must be non-zero".to_string()),
+        Ok(n) => Ok(n),
+        Err(err) => Err(format!("invalid size `{}`: {}", n, err)),
     };
-
-    (world, camera)
+    Ok((parse(width)?, parse(height)?))
 }
 
-fn build_scene() -> (World, impl Camera) {
-    let sampler = sampler::MultiJittered::new(256);
-    let view = ViewPlane::new(800, 600, 0.05, sampler.clone());
+fn main() {
+    let args = Args::parse();
+    // as in scene files, a white point only makes sense for the operator
+    // which uses it
+    if args.white.is_some() && args.tone_map != Some(ToneMapKind::ReinhardExtended) {
+        Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--white only applies to `--tone-map reinhard-extended`",
+            )
+            .exit();
+    }
 
-    let location = camera::Location {
-        eye: Vec3::new(-10.0, 5.0, 50.0),
-        centre: Vec3::new(0.0, 5.0, 0.0),
-        up: Vec3::new(0.0, 1.0, 0.0),
+    // the samplers are generated while loading, so this must happen first
+    if let Some(seed) = args.seed {
+        sampler::seed(seed);
+    }
+
+    // by default the image is named after the scene, in the current directory
+    let filename = match (&args.output, args.scene.file_name()) {
+        (Some(output), _) => output.clone(),
+        (None, Some(name)) => Path::new(name).with_extension("png"),
+        (None, None) => {
+            eprintln!(
+                "Can't name the image after the scene `{}`: use --output",
+                args.scene.display()
+            );
+            process::exit(1);
+        },
     };
-    let view_len = 40.0;
-    let camera = camera::ThinLens::new(location, view_len, 74.0, 1.0, 1.5, sampler);
-
-    let ambient = Box::new(light::Ambient::new(1.0));
-    let mut lights: Vec<Box<dyn Light>> = Vec::new();
-    lights.push(Box::new(light::PointLight::new(
-        4.0,
-        Vec3::new(-50.0, 50.0, 0.0),
-    )));
-    lights.push(Box::new(light::PointLight::with_colour(
-        3.0,
-        Vec3::new(50.0, 20.0, -30.0),
-        Colour::new(1.0, 1.0, 0.0),
-    )));
-
-    let mut objects: Vec<Box<dyn Geometry>> = vec![];
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(7.0, 4.0, 3.0),
-        radius: 4.0,
-        material: Matte::new(0.25, 0.65, Colour::white()),
-    }));
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(0.0, 4.0, -24.0),
-        radius: 4.0,
-        material: Matte::new(0.25, 0.65, Colour::new(0.3, 0.7, 1.0)),
-    }));
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(-7.0, 4.0, -51.0),
-        radius: 4.0,
-        material: Phong::new(0.25, 0.65, 0.1, 25.0, Colour::white()),
-    }));
-    objects.push(Box::new(geometry::Sphere {
-        centre: Vec3::new(-14.0, 4.0, -78.0),
-        radius: 4.0,
-        material: Phong::new(0.25, 0.65, 0.1, 25.0, Colour::white()),
-    }));
-    objects.push(Box::new(geometry::Plane {
-        point: Vec3::new(0.0, 0.0, 0.0),
-        normal: Vec3::new(0.0, 1.0, 0.0),
-        material: Matte::new(0.3, 0.5, Colour::new(0.3, 0.3, 0.3)),
-    }));
-    objects.push(Box::new(geometry::Cuboid {
-        min: Vec3::new(10.0, 0.0, -130.0),
-        max: Vec3::new(40.0, 15.0, -80.0),
-        material: Phong::new(0.25, 0.65, 0.2, 3.0, Colour::white()),
-    }));
-
-    let world = World {
-        objects,
-        background: Colour::new(0.7, 0.7, 1.0),
-        view,
-        ambient,
-        lights,
+    let overrides = Overrides {
+        resolution: args.resolution,
+        samples: args.samples,
+        camera: args.camera.clone(),
     };
+    let Scene { mut world, camera } = match Scene::load(&args.scene, &overrides) {
+        Ok(scene) => scene,
+        Err(err) => {
+            eprintln!("Failed to load scene: {}", err);
+            process::exit(1);
+        },
+    };
+    if let Some(kind) = args.tone_map {
+        world.view.display.tone_map = kind.tone_map(args.white);
+    }
+    if let Some(exposure) = args.exposure {
+        world.view.display.exposure = exposure;
+    }
 
-    (world, camera)
+    let mut options = RenderOptions {
+        progress: !args.quiet,
+        ..RenderOptions::default()
+    };
+    if let Some(threads) = args.threads {
+        options.threads = threads.get();
+    }
+    let tracer = args.tracer.tracer(&args, &world.view.sampler);
+    let now = Instant::now();
+
+    let film = camera.render_scene(&world, tracer.as_ref(), &options);
+
+    if !args.quiet {
+        let elapsed = now.elapsed().as_millis();
+        println!("Rendered in {} seconds.", elapsed as f64 / 1000.0);
+    }
+
+    match film.save(&filename, &world.view.display) {
+        Ok(_) if args.quiet => {},
+        Ok(_) => println!("Saved to \"{}\".", filename.display()),
+        Err(err) => {
+            eprintln!("Failed to save image: {}", err);
+            process::exit(1);
+        },
+    }
 }
@@ -6,16 +6,168 @@
 //! See the currently available [BRDFs](crate::brdf) for reflection functions
 //! used in materials.
 
-use crate::brdf::{GlossySpecular, Lambertian, BRDF};
-use crate::utility::{Colour, Ray};
+use crate::brdf::{FresnelReflector, GlossySpecular, Lambertian, PerfectSpecular, BRDF};
+use crate::btdf::{FresnelTransmitter, BTDF};
+use crate::sampler;
+use crate::utility::{Colour, Ray, Vec3};
 use crate::world::Intersection;
 
+use rand::Rng;
+
 use std::fmt::Debug;
+use std::sync::Arc;
 
 /// A material that can be applied to an object.
-pub trait Material: Debug {
+pub trait Material: Debug + Send + Sync {
     /// Returns the output colour of the point at the given intersection point.
     fn shade(&self, hit: &Intersection) -> Colour;
+
+    /// Returns only the light reflected directly from the world's lights.
+    ///
+    /// Unlike [`shade`](Material::shade), this leaves out any ambient light,
+    /// since it's used for global illumination where indirect light is traced
+    /// properly instead. By default nothing is reflected.
+    fn direct_shade(&self, _hit: &Intersection) -> Colour {
+        Colour::black()
+    }
+
+    /// Randomly chooses a direction to continue a path in, for global
+    /// illumination.
+    ///
+    /// Returns `None` if the path should stop here, which is the default.
+    fn sample_bounce(&self, _hit: &Intersection) -> Option<Bounce> {
+        None
+    }
+
+    /// The radiance emitted by the material, which is black for anything
+    /// that doesn't glow.
+    ///
+    /// Objects with emissive materials can be used as
+    /// [lights](crate::light::GeometryLight).
+    fn emission(&self) -> Colour {
+        Colour::black()
+    }
+}
+
+/// A direction chosen to continue a path in, from
+/// [`Material::sample_bounce`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounce {
+    pub direction: Vec3,
+    /// The weight for the light arriving from `direction`: the BRDF times the
+    /// cosine term, divided by the probability density of the direction.
+    pub weight: Colour,
+    /// Whether the bounce follows a reflection or transmission which
+    /// [`Material::direct_shade`] leaves out, like a mirror. Light emitted by
+    /// whatever the bounce hits must then be counted, since it wasn't already
+    /// found by sampling the lights.
+    pub specular: bool,
+    /// The fraction of each colour which remains after travelling a distance
+    /// of `1` in `direction`, for bounces into an absorbing medium. This is
+    /// white for no absorption.
+    pub filter: Colour,
+}
+
+/// Allows a single material to be shared between many objects.
+impl<M: Material + ?Sized> Material for Arc<M> {
+    fn shade(&self, hit: &Intersection) -> Colour {
+        (**self).shade(hit)
+    }
+
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        (**self).direct_shade(hit)
+    }
+
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        (**self).sample_bounce(hit)
+    }
+
+    fn emission(&self) -> Colour {
+        (**self).emission()
+    }
+}
+
+/// Sums the light from each of the world's lights that isn't in shadow.
+///
+/// The reflectance for each pair of incoming and outgoing directions is given
+/// by `f`.
+fn direct_light<F>(hit: &Intersection, f: F) -> Colour
+where
+    F: Fn(Vec3, Vec3) -> Colour,
+{
+    let out_dir = -hit.ray.direction;
+    hit.world
+        .lights
+        .iter()
+        .fold(Colour::black(), |accum, light| {
+            let sample = light.sample(hit);
+            let in_dir = sample.direction;
+            let angle = hit.normal.dot(in_dir);
+            if angle > 0.0 && sample.geometry > 0.0 && sample.pdf > 0.0 {
+                let shadow = Ray {
+                    origin: hit.hit_point,
+                    direction: in_dir,
+                };
+                if !light.in_shadow(shadow, &sample, hit.world) {
+                    let scale = angle * sample.geometry / sample.pdf;
+                    accum + f(in_dir, out_dir) * sample.radiance * scale
+                } else {
+                    accum
+                }
+            } else {
+                accum
+            }
+        })
+}
+
+/// Turns a sample from a BRDF into a bounce for a path.
+fn bounce_weight(
+    (f, in_dir, pdf): (Colour, Vec3, f64),
+    hit: &Intersection,
+    specular: bool,
+) -> Option<Bounce> {
+    let angle = hit.normal.dot(in_dir);
+    if pdf > 0.0 && angle > 0.0 {
+        Some(Bounce {
+            direction: in_dir,
+            weight: f * angle / pdf,
+            specular,
+            filter: Colour::white(),
+        })
+    } else {
+        None
+    }
+}
+
+/// Traces the light reflected from a sample of a BRDF, for recursive tracers.
+fn reflected_light(sample: (Colour, Vec3, f64), hit: &Intersection) -> Colour {
+    match bounce_weight(sample, hit, true) {
+        Some(bounce) => {
+            let ray = Ray {
+                origin: hit.hit_point,
+                direction: bounce.direction,
+            };
+            bounce.weight * hit.trace(ray)
+        },
+        None => Colour::black(),
+    }
+}
+
+/// Samples a bounce from either a reflection or the underlying material,
+/// with equal probability.
+fn mix_bounce(
+    reflection: impl FnOnce() -> Option<Bounce>,
+    base: impl FnOnce() -> Option<Bounce>,
+) -> Option<Bounce> {
+    let bounce = if sampler::rng().gen() {
+        reflection()
+    } else {
+        base()
+    };
+    bounce.map(|bounce| Bounce {
+        weight: bounce.weight * 2.0,
+        ..bounce
+    })
 }
 
 /// Matte objects, suitable for things like paper.
@@ -47,27 +199,21 @@ impl Matte {
 impl Material for Matte {
     fn shade(&self, hit: &Intersection) -> Colour {
         let out_dir = -hit.ray.direction;
-        let light = self.ambient.rho(hit, out_dir) * hit.world.ambient.radiance(hit);
+        let light =
+            self.ambient.rho(hit, out_dir) * hit.world.ambient.sample(hit).radiance;
+        light + self.direct_shade(hit)
+    }
 
-        hit.world.lights.iter().fold(light, |accum, light| {
-            let in_dir = light.direction(hit);
-            let angle = hit.normal.dot(in_dir);
-            if angle > 0.0 {
-                let shadow = Ray {
-                    origin: hit.hit_point,
-                    direction: in_dir,
-                };
-                if !light.in_shadow(shadow, hit.world) {
-                    let base_diffuse = self.diffuse.call(hit, in_dir, out_dir);
-                    accum + base_diffuse * light.radiance(hit) * angle
-                } else {
-                    accum
-                }
-            } else {
-                accum
-            }
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        direct_light(hit, |in_dir, out_dir| {
+            self.diffuse.call(hit, in_dir, out_dir)
         })
     }
+
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        let out_dir = -hit.ray.direction;
+        bounce_weight(self.diffuse.sample_f(hit, out_dir), hit, false)
+    }
 }
 
 /// Phong reflections, suitable for shiny objects like metal.
@@ -80,9 +226,24 @@ pub struct Phong {
 
 impl Phong {
     pub fn new(ka: f64, kd: f64, ks: f64, shininess: f64, colour: Colour) -> Self {
+        Self::with_specular_colour(ka, kd, ks, shininess, colour, colour)
+    }
+
+    /// Like [`Phong::new`], but the highlights have their own colour, rather
+    /// than taking on the colour of the surface.
+    ///
+    /// Plastics, for example, have white highlights whatever their colour.
+    pub fn with_specular_colour(
+        ka: f64,
+        kd: f64,
+        ks: f64,
+        shininess: f64,
+        colour: Colour,
+        specular_colour: Colour,
+    ) -> Self {
         let ambient = Lambertian::new(ka, colour);
         let diffuse = Lambertian::new(kd, colour);
-        let specular = GlossySpecular::new(ks, shininess, colour);
+        let specular = GlossySpecular::new(ks, shininess, specular_colour);
         Self {
             ambient,
             diffuse,
@@ -94,26 +255,375 @@ impl Phong {
 impl Material for Phong {
     fn shade(&self, hit: &Intersection) -> Colour {
         let out_dir = -hit.ray.direction;
-        let light = self.ambient.rho(hit, out_dir) * hit.world.ambient.radiance(hit);
+        let light =
+            self.ambient.rho(hit, out_dir) * hit.world.ambient.sample(hit).radiance;
+        light + self.direct_shade(hit)
+    }
 
-        hit.world.lights.iter().fold(light, |accum, light| {
-            let in_dir = light.direction(hit);
-            let angle = hit.normal.dot(in_dir);
-            if angle > 0.0 {
-                let shadow = Ray {
-                    origin: hit.hit_point,
-                    direction: in_dir,
-                };
-                if !light.in_shadow(shadow, hit.world) {
-                    let base_diffuse = self.diffuse.call(hit, in_dir, out_dir);
-                    let base_specular = self.specular.call(hit, in_dir, out_dir);
-                    accum + (base_diffuse + base_specular) * light.radiance(hit) * angle
-                } else {
-                    accum
-                }
-            } else {
-                accum
-            }
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        direct_light(hit, |in_dir, out_dir| {
+            self.diffuse.call(hit, in_dir, out_dir)
+                + self.specular.call(hit, in_dir, out_dir)
+        })
+    }
+
+    /// Picks either the diffuse or specular part with equal probability, and
+    /// samples a direction from that.
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        let out_dir = -hit.ray.direction;
+        let (f, in_dir, pdf) = if sampler::rng().gen() {
+            self.diffuse.sample_f(hit, out_dir)
+        } else {
+            self.specular.sample_f(hit, out_dir)
+        };
+        bounce_weight((f, in_dir, pdf * 0.5), hit, false)
+    }
+}
+
+/// Mirror-like objects, reflecting the world around them.
+///
+/// This adds [perfect specular reflection][1] on top of a [`Phong`] material.
+/// Reflections are traced recursively, so a tracer like
+/// [`Whitted`](crate::tracer::Whitted) is needed to see them.
+///
+/// [1]: crate::brdf::PerfectSpecular
+#[derive(Debug, Clone)]
+pub struct Reflective {
+    phong: Phong,
+    reflective: PerfectSpecular,
+}
+
+impl Reflective {
+    /// Construct a new reflective material.
+    ///
+    /// - `phong` is the base material
+    /// - `kr` is the reflectance of the mirror, between `0` and `1`
+    /// - `colour` tints the reflections
+    pub fn new(phong: Phong, kr: f64, colour: Colour) -> Self {
+        let reflective = PerfectSpecular::new(kr, colour);
+        Self { phong, reflective }
+    }
+}
+
+impl Material for Reflective {
+    fn shade(&self, hit: &Intersection) -> Colour {
+        let out_dir = -hit.ray.direction;
+        let reflected = reflected_light(self.reflective.sample_f(hit, out_dir), hit);
+        self.phong.shade(hit) + reflected
+    }
+
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        self.phong.direct_shade(hit)
+    }
+
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        let out_dir = -hit.ray.direction;
+        mix_bounce(
+            || bounce_weight(self.reflective.sample_f(hit, out_dir), hit, true),
+            || self.phong.sample_bounce(hit),
+        )
+    }
+}
+
+/// Objects with blurry reflections, like brushed metal.
+///
+/// This is like [`Reflective`], but reflected rays are scattered around the
+/// mirror direction according to a [Phong lobe][1], rather than following it
+/// exactly. Each ray only traces a single reflection, so many samples per
+/// pixel are needed for smooth results.
+///
+/// [1]: crate::brdf::GlossySpecular
+#[derive(Debug, Clone)]
+pub struct GlossyReflector {
+    phong: Phong,
+    glossy: GlossySpecular,
+}
+
+impl GlossyReflector {
+    /// Construct a new glossy reflector.
+    ///
+    /// - `phong` is the base material
+    /// - `kr` is the reflectance of the reflections
+    /// - `exponent` sets how sharp the reflections are: larger values are
+    ///   closer to a perfect mirror
+    /// - `colour` tints the reflections
+    pub fn new(phong: Phong, kr: f64, exponent: f64, colour: Colour) -> Self {
+        let glossy = GlossySpecular::normalised(kr, exponent, colour);
+        Self { phong, glossy }
+    }
+}
+
+impl Material for GlossyReflector {
+    fn shade(&self, hit: &Intersection) -> Colour {
+        let out_dir = -hit.ray.direction;
+        let reflected = reflected_light(self.glossy.sample_f(hit, out_dir), hit);
+        self.phong.shade(hit) + reflected
+    }
+
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        self.phong.direct_shade(hit)
+    }
+
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        let out_dir = -hit.ray.direction;
+        mix_bounce(
+            || bounce_weight(self.glossy.sample_f(hit, out_dir), hit, true),
+            || self.phong.sample_bounce(hit),
+        )
+    }
+}
+
+/// Transparent objects which refract light, like glass or water.
+///
+/// Light is both reflected and transmitted, in amounts given by the Fresnel
+/// equations, with total internal reflection where the light can't escape.
+/// Light travelling through either medium is absorbed following the
+/// Beer–Lambert law, giving a tint that deepens with thickness; light from
+/// the background is not absorbed on its way in. Recursive tracers are needed
+/// to see anything besides the highlights of the base [`Phong`] material.
+///
+/// Objects using this should be closed, with normals facing outwards.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::material::{Dielectric, Phong};
+/// use raytracer::utility::Colour;
+///
+/// // slightly green glass in air, with just a highlight from the Phong part
+/// let glass = Dielectric::new(
+///     Phong::new(0.0, 0.0, 0.2, 2000.0, Colour::white()),
+///     1.5,
+///     1.0,
+///     Colour::new(0.9, 1.0, 0.9),
+///     Colour::white(),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Dielectric {
+    phong: Phong,
+    reflector: FresnelReflector,
+    transmitter: FresnelTransmitter,
+    filter_in: Colour,
+    filter_out: Colour,
+}
+
+impl Dielectric {
+    /// Construct a new dielectric material.
+    ///
+    /// - `phong` is the base material, mostly useful for highlights
+    /// - `eta_in` and `eta_out` are the indices of refraction inside and
+    ///   outside of the object
+    /// - `filter_in` and `filter_out` are the fraction of each colour which
+    ///   remains after travelling a distance of `1` inside and outside of the
+    ///   object; use white for no absorption
+    pub fn new(
+        phong: Phong,
+        eta_in: f64,
+        eta_out: f64,
+        filter_in: Colour,
+        filter_out: Colour,
+    ) -> Self {
+        Self {
+            phong,
+            reflector: FresnelReflector::new(eta_in, eta_out),
+            transmitter: FresnelTransmitter::new(eta_in, eta_out),
+            filter_in,
+            filter_out,
+        }
+    }
+
+    /// The filter for light travelling in the given direction, through the
+    /// object or outside of it.
+    fn filter(&self, hit: &Intersection, direction: Vec3) -> Colour {
+        if direction.dot(hit.normal) < 0.0 {
+            self.filter_in
+        } else {
+            self.filter_out
+        }
+    }
+
+    /// Traces light arriving from the given direction, after absorption.
+    ///
+    /// Light from rays which escape without hitting anything isn't absorbed.
+    fn trace(&self, hit: &Intersection, direction: Vec3) -> Colour {
+        let ray = Ray {
+            origin: hit.hit_point,
+            direction,
+        };
+        match hit.trace_distance(ray) {
+            (light, Some(distance)) => self.filter(hit, direction).powf(distance) * light,
+            (light, None) => light,
+        }
+    }
+}
+
+impl Material for Dielectric {
+    fn shade(&self, hit: &Intersection) -> Colour {
+        let out_dir = -hit.ray.direction;
+        let light = self.phong.shade(hit);
+
+        let (fr, reflected, _) = self.reflector.sample_f(hit, out_dir);
+        let kr = fr * hit.normal.dot(reflected).abs();
+        let light = light + kr * self.trace(hit, reflected);
+
+        if self.transmitter.tir(hit, out_dir) {
+            light
+        } else {
+            let (ft, transmitted, _) = self.transmitter.sample_f(hit, out_dir);
+            let kt = ft * hit.normal.dot(transmitted).abs();
+            light + kt * self.trace(hit, transmitted)
+        }
+    }
+
+    fn direct_shade(&self, hit: &Intersection) -> Colour {
+        self.phong.direct_shade(hit)
+    }
+
+    /// Chooses between reflection and transmission in proportion to the
+    /// Fresnel reflectance. Indirect light through the base material is
+    /// ignored.
+    fn sample_bounce(&self, hit: &Intersection) -> Option<Bounce> {
+        let out_dir = -hit.ray.direction;
+        let (fr, reflected, _) = self.reflector.sample_f(hit, out_dir);
+        let kr = (fr * hit.normal.dot(reflected).abs()).max_channel();
+
+        let (direction, weight) = if sampler::rng().gen::<f64>() < kr {
+            (reflected, Colour::white())
+        } else {
+            let (ft, transmitted, _) = self.transmitter.sample_f(hit, out_dir);
+            let kt = ft * hit.normal.dot(transmitted).abs();
+            (transmitted, kt / (1.0 - kr))
+        };
+
+        Some(Bounce {
+            direction,
+            weight,
+            specular: true,
+            filter: self.filter(hit, direction),
         })
     }
 }
+
+/// A material which glows, emitting light evenly from the front of its
+/// surface.
+///
+/// It doesn't reflect any light. To light the rest of the world, the object
+/// must also be added as a [light](crate::light::GeometryLight); otherwise it
+/// is only seen directly.
+#[derive(Debug, Clone)]
+pub struct Emissive {
+    pub scale: f64,
+    pub colour: Colour,
+}
+
+impl Emissive {
+    pub fn new(scale: f64, colour: Colour) -> Self {
+        Self { scale, colour }
+    }
+}
+
+impl Material for Emissive {
+    fn shade(&self, hit: &Intersection) -> Colour {
+        if hit.normal.dot(hit.ray.direction) < 0.0 {
+            self.emission()
+        } else {
+            Colour::black()
+        }
+    }
+
+    fn emission(&self) -> Colour {
+        self.scale * self.colour
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::geometry::Sphere;
+    use crate::light::Ambient;
+    use crate::sampler::Jittered;
+    use crate::tracer::{PathTracer, Tracer, Whitted};
+    use crate::world::{ViewPlane, World};
+
+    /// The average brightness of a unit sphere made of `material`, lit only
+    /// by a white background, over `num_rays` rays at its visible half.
+    ///
+    /// A material that reflects all of the light arriving at it looks just
+    /// like the background, so this can't be more than one.
+    fn furnace<M: Material + 'static>(
+        material: M,
+        tracer: &dyn Tracer,
+        num_rays: u32,
+    ) -> f64 {
+        let sphere = Sphere {
+            centre: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material,
+        };
+        let world = World {
+            background: Colour::white(),
+            environment: None,
+            view: ViewPlane::new(1, 1, 1.0, Jittered::new(1)),
+            objects: Bvh::new(vec![Box::new(sphere)]),
+            ambient: Box::new(Ambient::new(0.0)),
+            lights: Vec::new(),
+        };
+
+        sampler::seed(3);
+        let mut rng = sampler::rng();
+        let total = (0..num_rays).fold(0.0, |total, _| {
+            let target =
+                Vec3::new(rng.gen_range(-0.7, 0.7), rng.gen_range(-0.7, 0.7), 0.0);
+            let origin = Vec3::new(0.0, 0.0, 5.0);
+            let ray = Ray {
+                origin,
+                direction: (target - origin).normalise(),
+            };
+            let colour = tracer.trace_ray(&world, ray, 0);
+            total + (colour.r + colour.g + colour.b) / 3.0
+        });
+        total / f64::from(num_rays)
+    }
+
+    /// Diffuse and mirror reflections add up to their total reflectance.
+    #[test]
+    fn reflective_conserves_energy() {
+        let phong = Phong::new(0.0, 0.3, 0.0, 1.0, Colour::white());
+        let material = Reflective::new(phong, 0.6, Colour::white());
+        let mean = furnace(material, &PathTracer::new(10, 3), 20_000);
+        assert!((mean - 0.9).abs() < 0.01, "mean radiance {}", mean);
+    }
+
+    /// Blurry reflections only reflect all of their reflectance when lit
+    /// head-on, and less at glancing angles, so they never reflect more.
+    #[test]
+    fn glossy_reflector_conserves_energy() {
+        let black = Phong::new(0.0, 0.0, 0.0, 1.0, Colour::white());
+        let material = GlossyReflector::new(black, 0.8, 100.0, Colour::white());
+        let mean = furnace(material, &PathTracer::new(10, 3), 20_000);
+        assert!(mean > 0.6 && mean < 0.8, "mean radiance {}", mean);
+
+        let phong = Phong::new(0.0, 0.2, 0.0, 1.0, Colour::white());
+        let material = GlossyReflector::new(phong, 0.8, 2.0, Colour::white());
+        let mean = furnace(material, &PathTracer::new(10, 3), 20_000);
+        assert!(mean < 1.0, "mean radiance {}", mean);
+    }
+
+    /// Clear glass splits light between reflection and refraction without
+    /// losing any, so a glass ball disappears against a white background.
+    #[test]
+    fn dielectric_conserves_energy() {
+        let glass = || {
+            let black = Phong::new(0.0, 0.0, 0.0, 1.0, Colour::white());
+            Dielectric::new(black, 1.5, 1.0, Colour::white(), Colour::white())
+        };
+
+        let mean = furnace(glass(), &Whitted::new(8), 500);
+        assert!(mean > 0.99 && mean < 1.0 + 1e-9, "mean radiance {}", mean);
+
+        let mean = furnace(glass(), &PathTracer::new(20, 3), 20_000);
+        assert!((mean - 1.0).abs() < 0.01, "mean radiance {}", mean);
+    }
+}
@@ -0,0 +1,364 @@
+//! Triangle meshes.
+//!
+//! A [`Mesh`] is a plain collection of shared vertex data, which can then be
+//! turned into renderable geometry with a [`TriangleMesh`].
+
+use crate::bvh::Bvh;
+use crate::geometry::{self, BoundingBox, Geometry, Hit};
+use crate::material::Material;
+use crate::utility::{Ray, Vec2, Vec3};
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Indexed vertex data for a collection of triangles.
+///
+/// Every face is a triple of indices into the vertex attributes, given in
+/// counter-clockwise order when viewed from the front of the face. All
+/// attributes share the same indices, so if `normals` or `uvs` are provided
+/// they must have the same length as `positions`.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    /// Vertex positions.
+    pub positions: Vec<Vec3>,
+    /// Per-vertex normals, used for smooth shading.
+    ///
+    /// If not provided, each face is shaded flat using its geometric normal.
+    pub normals: Option<Vec<Vec3>>,
+    /// Per-vertex texture coordinates.
+    pub uvs: Option<Vec<Vec2>>,
+    /// Indices of the vertices of each face.
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+    /// The positions of the vertices of the given face.
+    pub fn vertices(&self, face: usize) -> (Vec3, Vec3, Vec3) {
+        let [a, b, c] = self.faces[face];
+        (self.positions[a], self.positions[b], self.positions[c])
+    }
+
+    /// The geometric normal of the given face.
+    pub fn face_normal(&self, face: usize) -> Vec3 {
+        let (a, b, c) = self.vertices(face);
+        (b - a).cross(c - a).normalise()
+    }
+
+    /// The shading normal at a point on the given face.
+    ///
+    /// If the mesh has per-vertex normals they are interpolated across the
+    /// face, otherwise this is just the [face normal](Mesh::face_normal).
+    pub fn normal_at(&self, face: usize, pos: Vec3) -> Vec3 {
+        match &self.normals {
+            Some(normals) => {
+                let (a, b, c) = self.vertices(face);
+                let (u, v, w) = geometry::barycentric(pos, a, b, c);
+                let [i, j, k] = self.faces[face];
+                (u * normals[i] + v * normals[j] + w * normals[k]).normalise()
+            },
+            None => self.face_normal(face),
+        }
+    }
+
+    /// The interpolated texture coordinates at a point on the given face.
+    ///
+    /// Returns `None` if the mesh has no texture coordinates.
+    pub fn uv_at(&self, face: usize, pos: Vec3) -> Option<Vec2> {
+        self.uvs.as_ref().map(|uvs| {
+            let (a, b, c) = self.vertices(face);
+            let (u, v, w) = geometry::barycentric(pos, a, b, c);
+            let [i, j, k] = self.faces[face];
+            u * uvs[i] + v * uvs[j] + w * uvs[k]
+        })
+    }
+
+    /// The area of the given face.
+    pub fn face_area(&self, face: usize) -> f64 {
+        let (a, b, c) = self.vertices(face);
+        0.5 * (b - a).cross(c - a).mag()
+    }
+
+    /// The bounding box of the given face.
+    fn face_bounding_box(&self, face: usize) -> BoundingBox {
+        let (a, b, c) = self.vertices(face);
+        BoundingBox::empty().include(a).include(b).include(c)
+    }
+}
+
+/// Data shared between a mesh and all of its triangles.
+#[derive(Debug)]
+struct Shared<M: Material> {
+    mesh: Mesh,
+    material: M,
+}
+
+/// A single face of a [`TriangleMesh`].
+struct MeshTriangle<M: Material> {
+    shared: Arc<Shared<M>>,
+    face: usize,
+}
+
+impl<M: Material> fmt::Debug for MeshTriangle<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // don't print the whole mesh for every single face
+        f.debug_struct("MeshTriangle")
+            .field("face", &self.face)
+            .finish()
+    }
+}
+
+impl<M: Material> Geometry for MeshTriangle<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        let mesh = &self.shared.mesh;
+        let (a, b, c) = mesh.vertices(self.face);
+        geometry::intersect_triangle(ray, a, b, c).map(|t| Hit {
+            t,
+            normal: mesh.normal_at(self.face, ray.at(t)),
+            material: &self.shared.material,
+        })
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.shared.material
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(self.shared.mesh.face_bounding_box(self.face))
+    }
+
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let mesh = &self.shared.mesh;
+        let (a, b, c) = mesh.vertices(self.face);
+        let point = geometry::sample_triangle(sample, a, b, c);
+        let pdf = 1.0 / mesh.face_area(self.face);
+        Some((point, mesh.face_normal(self.face), pdf))
+    }
+}
+
+/// Renderable geometry made from a [`Mesh`].
+///
+/// Every face in the mesh is shaded with the same material. The faces are
+/// stored in their own [bounding volume hierarchy](crate::bvh), so large
+/// meshes only cost a handful of intersection tests per ray.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::geometry::Geometry;
+/// use raytracer::material::Matte;
+/// use raytracer::mesh::{Mesh, TriangleMesh};
+/// use raytracer::utility::{Colour, Ray, Vec3};
+///
+/// // a unit square on the `z = 0` plane, facing towards `+z`
+/// let mesh = Mesh {
+///     positions: vec![
+///         Vec3::new(0.0, 0.0, 0.0),
+///         Vec3::new(1.0, 0.0, 0.0),
+///         Vec3::new(1.0, 1.0, 0.0),
+///         Vec3::new(0.0, 1.0, 0.0),
+///     ],
+///     faces: vec![[0, 1, 2], [0, 2, 3]],
+///     ..Mesh::default()
+/// };
+/// let square = TriangleMesh::new(mesh, Matte::new(0.25, 0.65, Colour::white()));
+///
+/// // rays along the shared edge still hit
+/// let ray = Ray {
+///     origin: Vec3::new(0.5, 0.5, 1.0),
+///     direction: Vec3::new(0.0, 0.0, -1.0),
+/// };
+/// let hit = square.hit(&ray).unwrap();
+/// assert!((hit.t - 1.0).abs() < 1e-9);
+/// assert_eq!(hit.normal, Vec3::new(0.0, 0.0, 1.0));
+/// ```
+#[derive(Debug)]
+pub struct TriangleMesh<M: Material> {
+    shared: Arc<Shared<M>>,
+    triangles: Bvh,
+    /// The running total of the area of the faces, for sampling faces in
+    /// proportion to their area.
+    cumulative_areas: Vec<f64>,
+}
+
+impl<M: Material + 'static> TriangleMesh<M> {
+    /// Creates a new triangle mesh.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any face refers to a vertex that doesn't exist, or if the
+    /// normals or texture coordinates don't match up with the positions.
+    pub fn new(mesh: Mesh, material: M) -> Self {
+        let num_vertices = mesh.positions.len();
+        if let Some(normals) = &mesh.normals {
+            assert!(normals.len() == num_vertices, "wrong number of normals");
+        }
+        if let Some(uvs) = &mesh.uvs {
+            assert!(uvs.len() == num_vertices, "wrong number of uvs");
+        }
+        assert!(
+            mesh.faces.iter().flatten().all(|&i| i < num_vertices),
+            "face refers to a missing vertex"
+        );
+
+        let shared = Arc::new(Shared { mesh, material });
+        let triangles = (0..shared.mesh.faces.len())
+            .map(|face| -> Box<dyn Geometry> {
+                Box::new(MeshTriangle {
+                    shared: Arc::clone(&shared),
+                    face,
+                })
+            })
+            .collect();
+
+        let cumulative_areas = (0..shared.mesh.faces.len())
+            .scan(0.0, |total, face| {
+                *total += shared.mesh.face_area(face);
+                Some(*total)
+            })
+            .collect();
+
+        Self {
+            triangles: Bvh::new(triangles),
+            shared,
+            cumulative_areas,
+        }
+    }
+}
+
+impl<M: Material> TriangleMesh<M> {
+    /// The vertex data of the mesh.
+    pub fn mesh(&self) -> &Mesh {
+        &self.shared.mesh
+    }
+}
+
+impl<M: Material> Geometry for TriangleMesh<M> {
+    fn hit(&self, ray: &Ray) -> Option<Hit<'_>> {
+        self.triangles.hit(ray)
+    }
+
+    fn material(&self) -> &dyn Material {
+        &self.shared.material
+    }
+
+    /// A mesh without any faces has an empty box.
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let mesh = &self.shared.mesh;
+        let bbox = (0..mesh.faces.len())
+            .map(|face| mesh.face_bounding_box(face))
+            .fold(BoundingBox::empty(), BoundingBox::union);
+        Some(bbox)
+    }
+
+    /// Chooses a face in proportion to its area, then a point on that face.
+    fn sample_surface(&self, sample: Vec2) -> Option<(Vec3, Vec3, f64)> {
+        let mesh = &self.shared.mesh;
+        let total = *self.cumulative_areas.last()?;
+
+        // the part of the sample left over after choosing a face is reused
+        let target = sample.x * total;
+        let face = self
+            .cumulative_areas
+            .partition_point(|&area| area <= target)
+            .min(mesh.faces.len() - 1);
+        let start = if face == 0 {
+            0.0
+        } else {
+            self.cumulative_areas[face - 1]
+        };
+        let x = ((target - start) / mesh.face_area(face)).min(1.0);
+
+        let (a, b, c) = mesh.vertices(face);
+        let point = geometry::sample_triangle(Vec2::new(x, sample.y), a, b, c);
+        Some((point, mesh.face_normal(face), 1.0 / total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Matte;
+    use crate::utility::Colour;
+
+    fn matte() -> Matte {
+        Matte::new(0.25, 0.65, Colour::white())
+    }
+
+    /// A fan of four triangles around the origin on the `z = 0` plane.
+    fn fan() -> Mesh {
+        Mesh {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::new(0.0, -1.0, 0.0),
+            ],
+            faces: vec![[0, 1, 2], [0, 2, 3], [0, 3, 4], [0, 4, 1]],
+            ..Mesh::default()
+        }
+    }
+
+    /// Rays through shared edges and the shared vertex never slip through.
+    #[test]
+    fn watertight() {
+        let mesh = TriangleMesh::new(fan(), matte());
+        for &(x, y) in &[(0.0, 0.0), (0.5, 0.0), (0.0, -0.5), (-0.25, 0.0)] {
+            let ray = Ray {
+                origin: Vec3::new(x, y, 1.0),
+                direction: Vec3::new(0.3, -0.2, -1.0),
+            };
+            let hit = mesh.hit(&ray).expect("ray slipped through");
+            assert!((hit.t - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn interpolated_normals() {
+        let mut fan = fan();
+        let tilt = |x: f64, y: f64| Vec3::new(x, y, 1.0).normalise();
+        fan.normals = Some(vec![
+            tilt(0.0, 0.0),
+            tilt(1.0, 0.0),
+            tilt(0.0, 1.0),
+            tilt(-1.0, 0.0),
+            tilt(0.0, -1.0),
+        ]);
+        let mesh = TriangleMesh::new(fan, matte());
+        let normal_at = |x: f64, y: f64| {
+            let ray = Ray {
+                origin: Vec3::new(x, y, 1.0),
+                direction: Vec3::new(0.0, 0.0, -1.0),
+            };
+            mesh.hit(&ray).unwrap().normal
+        };
+
+        // at the vertices the normals are exactly those given
+        assert!((normal_at(0.0, 0.0) - tilt(0.0, 0.0)).mag() < 1e-12);
+        assert!((normal_at(1.0, 0.0) - tilt(1.0, 0.0)).mag() < 1e-12);
+        // halfway along an edge they're the normalised average
+        let expected = (tilt(1.0, 0.0) + tilt(0.0, 1.0)).normalise();
+        assert!((normal_at(0.5, 0.5) - expected).mag() < 1e-12);
+        // and always normalised
+        let normal = normal_at(0.2, 0.3);
+        assert!((normal.mag() - 1.0).abs() < 1e-12);
+
+        // without normals, faces are flat
+        let flat = TriangleMesh::new(self::fan(), matte());
+        let ray = Ray {
+            origin: Vec3::new(0.2, 0.3, 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        };
+        assert_eq!(flat.hit(&ray).unwrap().normal, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    /// A mesh without faces can't be hit, so it's left out of hierarchies
+    /// rather than treated as unbounded.
+    #[test]
+    fn empty_mesh() {
+        let mesh = TriangleMesh::new(Mesh::default(), matte());
+        assert!(mesh.bounding_box().unwrap().is_empty());
+        let bvh = Bvh::new(vec![Box::new(mesh)]);
+        assert!(bvh.is_empty());
+    }
+}
@@ -0,0 +1,664 @@
+//! Loading models from Wavefront OBJ files.
+//!
+//! Supports the common subset of the format exported by most modelling tools:
+//! vertex positions, normals and texture coordinates, polygonal faces (which
+//! are split into triangles), and materials from MTL libraries. Other
+//! statements, like groups and smoothing groups, are ignored.
+//!
+//! MTL materials are mapped onto the materials provided by this crate: see
+//! [`MtlMaterial`] for details.
+
+use crate::geometry::Geometry;
+use crate::material::{Matte, Phong};
+use crate::mesh::{Mesh, TriangleMesh};
+use crate::utility::{Colour, Vec2, Vec3};
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+
+/// Specular exponent used for materials which have a specular colour `Ks` but
+/// no `Ns`.
+pub const DEFAULT_SHININESS: f64 = 32.0;
+
+/// Errors that can occur while loading a model.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// A file was malformed.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { .. } => None,
+        }
+    }
+}
+
+/// The properties of a material read from an MTL library.
+///
+/// These are mapped onto materials as follows:
+///
+/// - the diffuse colour `Kd` is used as the base colour of the material
+/// - the ambient colour `Ka` gives the ambient reflectance, relative to the
+///   diffuse colour
+/// - if the specular colour `Ks` is not black, and the illumination model
+///   `illum` doesn't disable highlights, then a [`Phong`] material is used
+///   with highlights of that colour and the specular exponent `Ns`; otherwise
+///   the material is [`Matte`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlMaterial {
+    /// Ambient colour, `Ka`.
+    pub ambient: Colour,
+    /// Diffuse colour, `Kd`.
+    pub diffuse: Colour,
+    /// Specular colour, `Ks`.
+    pub specular: Colour,
+    /// Specular exponent, `Ns`.
+    pub shininess: f64,
+    /// Whether the illumination model includes specular highlights.
+    pub highlights: bool,
+}
+
+impl Default for MtlMaterial {
+    /// The defaults given by the MTL specification, except that there is no
+    /// specular highlight unless requested, and the specular exponent is
+    /// [`DEFAULT_SHININESS`] rather than zero, which would light the whole
+    /// surface evenly instead of giving a highlight.
+    fn default() -> Self {
+        Self {
+            ambient: Colour::new(0.2, 0.2, 0.2),
+            diffuse: Colour::new(0.8, 0.8, 0.8),
+            specular: Colour::black(),
+            shininess: DEFAULT_SHININESS,
+            highlights: true,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Creates renderable geometry for the given mesh using this material.
+    pub fn build(&self, mesh: Mesh) -> Box<dyn Geometry> {
+        fn mean(c: Colour) -> f64 {
+            (c.r + c.g + c.b) / 3.0
+        }
+
+        let kd = mean(self.diffuse);
+        let ka = if kd > 0.0 {
+            mean(self.ambient) / kd
+        } else {
+            0.0
+        };
+
+        if self.highlights && self.specular.max_channel() > 0.0 {
+            let material = Phong::with_specular_colour(
+                ka,
+                1.0,
+                1.0,
+                self.shininess,
+                self.diffuse,
+                self.specular,
+            );
+            Box::new(TriangleMesh::new(mesh, material))
+        } else {
+            let material = Matte::new(ka, 1.0, self.diffuse);
+            Box::new(TriangleMesh::new(mesh, material))
+        }
+    }
+}
+
+/// A group of faces sharing the same material.
+#[derive(Debug, Clone)]
+pub struct Group {
+    /// The name of the material, if any was specified.
+    pub material: Option<String>,
+    /// The triangles in the group.
+    pub mesh: Mesh,
+}
+
+/// A model loaded from an OBJ file.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::obj::Model;
+/// use std::path::Path;
+///
+/// let source = "
+///     v 0 0 0
+///     v 1 0 0
+///     v 1 1 0
+///     v 0 1 0
+///     f 1 2 3 4
+/// ";
+/// let model = Model::parse(source, Path::new("square.obj")).unwrap();
+/// assert_eq!(model.groups[0].mesh.faces.len(), 2);
+///
+/// let err = Model::parse("f 1 2 3", Path::new("bad.obj")).unwrap_err();
+/// assert_eq!(err.to_string(), "bad.obj:1: vertex index 1 out of range");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    /// The faces of the model, grouped by material.
+    pub groups: Vec<Group>,
+    /// All materials loaded from the model's material libraries.
+    pub materials: HashMap<String, MtlMaterial>,
+}
+
+impl Model {
+    /// Loads a model from the OBJ file at `path`.
+    ///
+    /// Any material libraries are loaded relative to the directory of the OBJ
+    /// file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::parse(&read(path)?, path)
+    }
+
+    /// Parses a model from the contents of an OBJ file.
+    ///
+    /// `path` is used for error messages and to find material libraries; it
+    /// doesn't need to exist unless the model uses materials.
+    pub fn parse(source: &str, path: &Path) -> Result<Self, Error> {
+        ObjParser::new(path).parse(source)
+    }
+
+    /// Creates renderable geometry for the model, suitable for placing in a
+    /// [`World`](crate::world::World).
+    ///
+    /// Groups without a material, or whose material isn't in any of the
+    /// model's libraries, use the [default](MtlMaterial::default) material.
+    pub fn into_geometry(self) -> Vec<Box<dyn Geometry>> {
+        let materials = self.materials;
+        let default = MtlMaterial::default();
+        self.groups
+            .into_iter()
+            .filter(|group| !group.mesh.faces.is_empty())
+            .map(|group| {
+                let material = group
+                    .material
+                    .as_ref()
+                    .and_then(|name| materials.get(name))
+                    .unwrap_or(&default);
+                material.build(group.mesh)
+            })
+            .collect()
+    }
+}
+
+/// Loads all materials from the MTL library at `path`.
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, MtlMaterial>, Error> {
+    let path = path.as_ref();
+    parse_mtl(&read(path)?, path)
+}
+
+/// Parses all materials from the contents of an MTL library.
+///
+/// `path` is only used for error messages.
+pub fn parse_mtl(
+    source: &str,
+    path: &Path,
+) -> Result<HashMap<String, MtlMaterial>, Error> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let mut line = Line::new(path, index + 1, line);
+        let keyword = match line.keyword() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = line.rest("material name")?;
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name, MtlMaterial::default()));
+            continue;
+        }
+
+        let material = match &mut current {
+            Some((_, material)) => material,
+            None => return Err(line.error(format!("`{}` before `newmtl`", keyword))),
+        };
+
+        match keyword {
+            "Ka" => material.ambient = line.colour()?,
+            "Kd" => material.diffuse = line.colour()?,
+            "Ks" => material.specular = line.colour()?,
+            "Ns" => material.shininess = line.number("specular exponent")?,
+            "illum" => {
+                let model: u32 = line.parse("illumination model")?;
+                material.highlights = model >= 2;
+            },
+            // textures, transparency, etc. aren't supported
+            _ => continue,
+        }
+        line.finish()?;
+    }
+
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+/// Reads a whole file into a string.
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_owned(),
+        source,
+    })
+}
+
+/// A single line of an OBJ or MTL file, split into whitespace-separated
+/// tokens.
+struct Line<'a> {
+    path: &'a Path,
+    number: usize,
+    tokens: SplitWhitespace<'a>,
+}
+
+impl<'a> Line<'a> {
+    fn new(path: &'a Path, number: usize, text: &'a str) -> Self {
+        // strip comments
+        let text = text.split('#').next().unwrap_or("");
+        Self {
+            path,
+            number,
+            tokens: text.split_whitespace(),
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Parse {
+            path: self.path.to_owned(),
+            line: self.number,
+            message,
+        }
+    }
+
+    /// The statement keyword, or `None` if the line is blank.
+    fn keyword(&mut self) -> Option<&'a str> {
+        self.tokens.next()
+    }
+
+    /// Parses the next token as a value of type `T`.
+    fn parse<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, Error> {
+        match self.tokens.next() {
+            Some(token) => token
+                .parse()
+                .map_err(|_| self.error(format!("invalid {} `{}`", what, token))),
+            None => Err(self.error(format!("missing {}", what))),
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<f64, Error> {
+        let n: f64 = self.parse(what)?;
+        if n.is_finite() {
+            Ok(n)
+        } else {
+            Err(self.error(format!("invalid {} `{}`", what, n)))
+        }
+    }
+
+    /// Parses the next token as a number if there is one, otherwise returns
+    /// `default`.
+    fn optional_number(&mut self, what: &str, default: f64) -> Result<f64, Error> {
+        if self.tokens.clone().next().is_some() {
+            self.number(what)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn vector(&mut self) -> Result<Vec3, Error> {
+        let x = self.number("x coordinate")?;
+        let y = self.number("y coordinate")?;
+        let z = self.number("z coordinate")?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    /// Parses an MTL colour: either a single grey value, or three RGB values.
+    fn colour(&mut self) -> Result<Colour, Error> {
+        let r = self.number("colour")?;
+        let g = self.optional_number("colour", r)?;
+        let b = self.optional_number("colour", r)?;
+        Ok(Colour::new(r, g, b))
+    }
+
+    /// Everything remaining on the line, for names that may contain spaces.
+    fn rest(&mut self, what: &str) -> Result<String, Error> {
+        let rest = self.tokens.by_ref().collect::<Vec<_>>().join(" ");
+        if rest.is_empty() {
+            Err(self.error(format!("missing {}", what)))
+        } else {
+            Ok(rest)
+        }
+    }
+
+    /// Ensures there is nothing left on the line.
+    fn finish(&mut self) -> Result<(), Error> {
+        match self.tokens.next() {
+            Some(token) => Err(self.error(format!("unexpected `{}`", token))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The indices of the attributes of a single vertex of a face, after
+/// resolving relative indices.
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+/// A group of faces being built up.
+#[derive(Default)]
+struct GroupBuilder {
+    material: Option<String>,
+    mesh: Mesh,
+    /// Maps vertex attributes in the file to indices in the mesh.
+    vertices: HashMap<VertexKey, usize>,
+    has_normals: bool,
+    has_uvs: bool,
+}
+
+/// State while parsing an OBJ file.
+struct ObjParser<'a> {
+    path: &'a Path,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    groups: Vec<GroupBuilder>,
+    materials: HashMap<String, MtlMaterial>,
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(path: &'a Path) -> Self {
+        Self {
+            path,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            groups: vec![GroupBuilder {
+                has_normals: true,
+                has_uvs: true,
+                ..GroupBuilder::default()
+            }],
+            materials: HashMap::new(),
+        }
+    }
+
+    fn parse(mut self, source: &str) -> Result<Model, Error> {
+        for (index, line) in source.lines().enumerate() {
+            let mut line = Line::new(self.path, index + 1, line);
+            let keyword = match line.keyword() {
+                Some(keyword) => keyword,
+                None => continue,
+            };
+
+            match keyword {
+                "v" => {
+                    let v = line.vector()?;
+                    // ignore the optional weight
+                    line.optional_number("w coordinate", 1.0)?;
+                    self.positions.push(v);
+                },
+                "vn" => {
+                    let normal = line.vector()?;
+                    if normal.mag() == 0.0 {
+                        return Err(line.error("normal must be non-zero".to_string()));
+                    }
+                    self.normals.push(normal.normalise());
+                },
+                "vt" => {
+                    let u = line.number("u coordinate")?;
+                    let v = line.optional_number("v coordinate", 0.0)?;
+                    line.optional_number("w coordinate", 0.0)?;
+                    self.uvs.push(Vec2::new(u, v));
+                },
+                "f" => self.face(&mut line)?,
+                // unknown materials use the default, as many exporters
+                // reference materials without providing a library
+                "usemtl" => self.use_material(line.rest("material name")?),
+                "mtllib" => {
+                    let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+                    // library names can't contain spaces, but there may be
+                    // several of them
+                    for name in line.rest("material library")?.split_whitespace() {
+                        self.materials.extend(load_mtl(dir.join(name))?);
+                    }
+                },
+                // groups, objects, smoothing groups, lines, curves, etc.
+                _ => continue,
+            }
+            line.finish()?;
+        }
+
+        let groups = self
+            .groups
+            .into_iter()
+            .filter(|group| !group.mesh.faces.is_empty())
+            .map(GroupBuilder::finish)
+            .collect();
+
+        Ok(Model {
+            groups,
+            materials: self.materials,
+        })
+    }
+
+    /// Switches to the group using the named material, creating it if needed.
+    fn use_material(&mut self, name: String) {
+        let name = Some(name);
+        match self.groups.iter().position(|g| g.material == name) {
+            Some(index) => {
+                // keep the current group last
+                let group = self.groups.remove(index);
+                self.groups.push(group);
+            },
+            None => self.groups.push(GroupBuilder {
+                material: name,
+                has_normals: true,
+                has_uvs: true,
+                ..GroupBuilder::default()
+            }),
+        }
+    }
+
+    /// Parses a face, splitting it into a fan of triangles.
+    ///
+    /// Degenerate triangles, with no area, are skipped since they have no
+    /// normal and can never be hit.
+    fn face(&mut self, line: &mut Line) -> Result<(), Error> {
+        let mut indices = Vec::new();
+        while let Some(token) = line.tokens.next() {
+            let key = self.vertex_key(line, token)?;
+            let group = self.groups.last_mut().expect("no current group");
+
+            group.has_uvs &= key.1.is_some();
+            group.has_normals &= key.2.is_some();
+
+            let next = group.vertices.len();
+            let index = *group.vertices.entry(key).or_insert(next);
+            if index == next {
+                group.mesh.positions.push(self.positions[key.0]);
+                group
+                    .mesh
+                    .uvs
+                    .get_or_insert_with(Vec::new)
+                    .push(match key.1 {
+                        Some(i) => self.uvs[i],
+                        None => Vec2::new(0.0, 0.0),
+                    });
+                let normals = group.mesh.normals.get_or_insert_with(Vec::new);
+                normals.push(match key.2 {
+                    Some(i) => self.normals[i],
+                    None => Vec3::new(0.0, 0.0, 0.0),
+                });
+            }
+            indices.push(index);
+        }
+
+        if indices.len() < 3 {
+            return Err(line.error("face has fewer than three vertices".to_string()));
+        }
+
+        let mesh = &mut self.groups.last_mut().expect("no current group").mesh;
+        for i in 1..indices.len() - 1 {
+            let face = [indices[0], indices[i], indices[i + 1]];
+            let [a, b, c] = face.map(|i| mesh.positions[i]);
+            if (b - a).cross(c - a).mag() > 0.0 {
+                mesh.faces.push(face);
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a face vertex of the form `v`, `v/vt`, `v//vn`, or `v/vt/vn`.
+    fn vertex_key(&self, line: &Line, token: &str) -> Result<VertexKey, Error> {
+        let mut parts = token.split('/');
+        let resolve = |part: Option<&str>, count: usize, what: &str| {
+            match part {
+                None | Some("") => Ok(None),
+                Some(part) => {
+                    let index: i64 = part.parse().map_err(|_| {
+                        line.error(format!("invalid {} index `{}`", what, part))
+                    })?;
+                    // indices start at one, and negative indices are relative
+                    // to the end of the list so far
+                    let resolved = if index > 0 {
+                        index - 1
+                    } else {
+                        count as i64 + index
+                    };
+                    if index == 0 || resolved < 0 || resolved >= count as i64 {
+                        Err(line.error(format!("{} index {} out of range", what, index)))
+                    } else {
+                        Ok(Some(resolved as usize))
+                    }
+                },
+            }
+        };
+
+        let position = resolve(parts.next(), self.positions.len(), "vertex")?
+            .ok_or_else(|| line.error(format!("missing vertex index in `{}`", token)))?;
+        let uv = resolve(parts.next(), self.uvs.len(), "texture coordinate")?;
+        let normal = resolve(parts.next(), self.normals.len(), "normal")?;
+
+        if parts.next().is_some() {
+            return Err(line.error(format!("invalid face vertex `{}`", token)));
+        }
+        Ok((position, uv, normal))
+    }
+}
+
+impl GroupBuilder {
+    /// Finishes building the group, dropping any attributes that weren't
+    /// provided for every vertex.
+    fn finish(mut self) -> Group {
+        if !self.has_normals {
+            self.mesh.normals = None;
+        }
+        if !self.has_uvs {
+            self.mesh.uvs = None;
+        }
+        Group {
+            material: self.material,
+            mesh: self.mesh,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `source`, expecting a parse error, and returns its line number
+    /// and message.
+    fn parse_error(source: &str) -> (usize, String) {
+        match Model::parse(source, Path::new("test.obj")) {
+            Err(Error::Parse { line, message, .. }) => (line, message),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("parsed malformed model"),
+        }
+    }
+
+    #[test]
+    fn bad_index() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n";
+        assert_eq!(
+            parse_error(source),
+            (4, "vertex index 4 out of range".into())
+        );
+
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 0\n";
+        assert_eq!(
+            parse_error(source),
+            (4, "vertex index 0 out of range".into())
+        );
+
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//2 3//1\n";
+        assert_eq!(
+            parse_error(source),
+            (5, "normal index 2 out of range".into())
+        );
+
+        let source = "v 0 0 0\nf 1 x 1\n";
+        assert_eq!(parse_error(source), (2, "invalid vertex index `x`".into()));
+    }
+
+    #[test]
+    fn zero_normal() {
+        let source = "v 0 0 0\n\nvn 0 0 0\n";
+        assert_eq!(parse_error(source), (3, "normal must be non-zero".into()));
+    }
+
+    #[test]
+    fn missing_mtl() {
+        let source = "mtllib missing.mtl\n";
+        let path = Path::new("/nonexistent/test.obj");
+        match Model::parse(source, path) {
+            Err(Error::Io { path, source }) => {
+                assert_eq!(path, Path::new("/nonexistent/missing.mtl"));
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+            },
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("loaded missing material library"),
+        }
+    }
+
+    #[test]
+    fn mtl_before_newmtl() {
+        match parse_mtl("Kd 1 0 0\n", Path::new("test.mtl")) {
+            Err(Error::Parse { line, message, .. }) => {
+                assert_eq!((line, message.as_str()), (1, "`Kd` before `newmtl`"));
+            },
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
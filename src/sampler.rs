@@ -12,8 +12,85 @@
 //! In the meantime, this will suffice.
 
 use crate::utility::{Vec2, Vec3};
-use rand::{distributions::Uniform, seq::SliceRandom, thread_rng, Rng};
-use std::{f64, fmt::Debug};
+use rand::{
+    distributions::Uniform, rngs::StdRng, seq::SliceRandom, Rng, RngCore, SeedableRng,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::hash_map::DefaultHasher,
+    f64,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    sync::Arc,
+};
+
+thread_local! {
+    static RNG: Rc<RefCell<StdRng>> = Rc::new(RefCell::new(StdRng::from_entropy()));
+    static PIXEL_SAMPLE: Cell<Option<PixelSample>> = const { Cell::new(None) };
+}
+
+/// Seeds the random number generator used for sampling on the current thread.
+///
+/// By default the generator is seeded from the operating system, so each run
+/// produces slightly different results. After calling this the same sequence
+/// of samples will be generated every time.
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Returns a handle to the random number generator used for sampling on the
+/// current thread.
+///
+/// This works just like [`rand::thread_rng`], except that it can be
+/// [seeded](seed) for reproducible results.
+pub fn rng() -> SampleRng {
+    SampleRng(RNG.with(Rc::clone))
+}
+
+/// The random number generator used for sampling.
+///
+/// See [`rng`] for details.
+#[derive(Debug, Clone)]
+pub struct SampleRng(Rc<RefCell<StdRng>>);
+
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.borrow_mut().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.borrow_mut().try_fill_bytes(dest)
+    }
+}
+
+/// The pixel sample being traced on a thread; see [`with_pixel_sample`].
+#[derive(Debug, Clone, Copy)]
+struct PixelSample {
+    pixel: u64,
+    index: usize,
+}
+
+/// Runs `f` while tracing sample number `index` of a pixel, so that any
+/// [`Samples::sample`] calls made by `f` pick from the pixel's sample sets.
+///
+/// `pixel` identifies the pixel; it should be random, so that different renders
+/// pick different sets for the same pixel.
+pub fn with_pixel_sample<R>(pixel: u64, index: usize, f: impl FnOnce() -> R) -> R {
+    let sample = Some(PixelSample { pixel, index });
+    let previous = PIXEL_SAMPLE.with(|cell| cell.replace(sample));
+    let result = f();
+    PIXEL_SAMPLE.with(|cell| cell.set(previous));
+    result
+}
 
 /// Number of sets of samples to generate.
 ///
@@ -42,7 +119,7 @@ const NUM_SETS: usize = 83;
 /// let mut sample_set = gen.gen_square_samples();
 /// let s = sample_set.get_next();
 /// ```
-pub trait Generator: Debug {
+pub trait Generator: Debug + Send + Sync {
     /// The number of samples in each set.
     fn num_samples(&self) -> usize;
 
@@ -96,6 +173,33 @@ pub trait Generator: Debug {
     }
 }
 
+/// Allows choosing a generator at runtime.
+impl<G: Generator + ?Sized> Generator for Box<G> {
+    fn num_samples(&self) -> usize {
+        (**self).num_samples()
+    }
+
+    fn num_sets(&self) -> usize {
+        (**self).num_sets()
+    }
+
+    fn new_square_set(&self) -> Vec<Vec2> {
+        (**self).new_square_set()
+    }
+
+    fn gen_square_samples(&self) -> Samples<Vec2> {
+        (**self).gen_square_samples()
+    }
+
+    fn gen_disc_samples(&self) -> Samples<Vec2> {
+        (**self).gen_disc_samples()
+    }
+
+    fn gen_hemisphere_samples(&self, e: f64) -> Samples<Vec3> {
+        (**self).gen_hemisphere_samples(e)
+    }
+}
+
 /// The default sampler to use if you're not fussed otherwise.
 pub type Default = MultiJittered;
 
@@ -120,7 +224,7 @@ impl Generator for Random {
     }
 
     fn new_square_set(&self) -> Vec<Vec2> {
-        let mut rng = thread_rng();
+        let mut rng = rng();
         (0..self.num_samples)
             .map(|_| Vec2::new(rng.gen(), rng.gen()))
             .collect()
@@ -149,7 +253,7 @@ impl Generator for Jittered {
     }
 
     fn new_square_set(&self) -> Vec<Vec2> {
-        let mut rng = thread_rng();
+        let mut rng = rng();
         self.0
             .new_square_set()
             .into_iter()
@@ -203,8 +307,8 @@ impl Generator for Regular {
                 s.push(Vec2::new(x, y) / (self.n as f64));
             }
         }
-        s.shuffle(&mut thread_rng());
-        return s;
+        s.shuffle(&mut rng());
+        s
     }
 }
 
@@ -232,7 +336,7 @@ impl Generator for NRooks {
     }
 
     fn new_square_set(&self) -> Vec<Vec2> {
-        let mut rng = thread_rng();
+        let mut rng = rng();
         let mut xs: Vec<_> = (0..self.num_samples).collect();
         let mut ys: Vec<_> = (0..self.num_samples).collect();
 
@@ -240,7 +344,7 @@ impl Generator for NRooks {
         ys.shuffle(&mut rng);
 
         xs.into_iter()
-            .zip(ys.into_iter())
+            .zip(ys)
             .map(|(x, y)| {
                 let x = (x as f64) + rng.gen::<f64>();
                 let y = (y as f64) + rng.gen::<f64>();
@@ -284,7 +388,7 @@ impl Generator for MultiJittered {
     }
 
     fn new_square_set(&self) -> Vec<Vec2> {
-        let mut rng = thread_rng();
+        let mut rng = rng();
         let mut xs = Vec::with_capacity(self.num_samples);
         let mut ys = Vec::with_capacity(self.num_samples);
 
@@ -324,12 +428,12 @@ impl Generator for MultiJittered {
         // join the coordinates and shuffle total order
         let mut v = xs
             .into_iter()
-            .zip(ys.into_iter())
+            .zip(ys)
             .map(|(x, y)| Vec2::new(x, y))
             .collect::<Vec<_>>();
 
         v.shuffle(&mut rng);
-        return v;
+        v
     }
 }
 
@@ -363,7 +467,7 @@ impl Generator for Hammersley {
                 j /= 2;
                 f *= 0.5;
             }
-            return x;
+            x
         }
 
         let n = self.num_samples as f64;
@@ -371,8 +475,8 @@ impl Generator for Hammersley {
             .map(|i| Vec2::new(i as f64 / n, phi(i)))
             .collect::<Vec<_>>();
 
-        v.shuffle(&mut thread_rng());
-        return v;
+        v.shuffle(&mut rng());
+        v
     }
 }
 
@@ -398,9 +502,12 @@ impl Generator for Hammersley {
 ///     // ...
 /// }
 /// ```
+///
+/// The sets themselves are shared between clones, so cloning only copies the
+/// order they're visited in.
 #[derive(Clone, Debug)]
 pub struct Samples<T> {
-    samples: Vec<Vec<T>>,
+    samples: Arc<[Vec<T>]>,
     num_samples: usize,
     count: usize,
     indices: Vec<usize>,
@@ -412,7 +519,7 @@ impl<T: Clone> Samples<T> {
         assert!(num_samples == samples[0].len());
         Self {
             num_samples,
-            samples,
+            samples: samples.into(),
             count: 0,
             indices: (0..num_sets).collect(),
         }
@@ -439,11 +546,63 @@ impl<T: Clone> Samples<T> {
             // For now this is fine and intuitive though.
             if self.count == self.indices.len() {
                 self.count = 0;
-                self.indices.shuffle(&mut thread_rng());
+                self.indices.shuffle(&mut rng());
             }
         }
         self.samples.get(self.indices[self.count]).unwrap()
     }
+
+    /// Returns another copy of these samples, which visits the same sets in a
+    /// fresh random order.
+    ///
+    /// This lets several users, such as the tiles of an image, share one set
+    /// of samples without all going through them in step.
+    pub fn shuffled(&self) -> Self {
+        let mut indices = self.indices.clone();
+        indices.shuffle(&mut rng());
+        Self {
+            samples: Arc::clone(&self.samples),
+            num_samples: self.num_samples,
+            count: 0,
+            indices,
+        }
+    }
+
+    /// Picks a single sample for the pixel sample currently being traced.
+    ///
+    /// Unlike [`Samples::get_next`] this doesn't need mutable access, so
+    /// lights and materials can share their samples between threads. Within
+    /// [`with_pixel_sample`], successive samples of a pixel walk through one
+    /// set, so that they're spread as evenly as the [`Generator`] allows,
+    /// before moving on to another set. Each `stream`, such as the depth of
+    /// a ray, walks through its own sets, so that the samples taken at one
+    /// bounce aren't correlated with those at the next.
+    ///
+    /// This is how lights and materials should take their samples: each pixel
+    /// is stratified as well as it would be by [`Samples::get_next`], and
+    /// since every pixel picks its own sets the fixed number of sets doesn't
+    /// show up as a pattern in the image.
+    ///
+    /// Outside of [`with_pixel_sample`] any sample may be picked at random.
+    pub fn sample(&self, stream: u32) -> T {
+        let PixelSample { pixel, index } = match PIXEL_SAMPLE.with(Cell::get) {
+            Some(sample) => sample,
+            None => {
+                let mut rng = rng();
+                let set = self.samples.choose(&mut rng).unwrap();
+                return set.choose(&mut rng).unwrap().clone();
+            },
+        };
+
+        let mut hasher = DefaultHasher::new();
+        (pixel, stream, index / self.num_samples).hash(&mut hasher);
+        let hash = hasher.finish() as usize;
+
+        // offset within the set too, so that the same elements of different
+        // samples aren't always used together
+        let set = &self.samples[hash % self.samples.len()];
+        set[(index + hash / self.samples.len()) % self.num_samples].clone()
+    }
 }
 
 /// Given a sample on the unit square, transform it to lie on the unit disk.
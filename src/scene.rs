@@ -0,0 +1,2009 @@
+//! Loading scenes from description files.
+//!
+//! Scenes are described using [TOML](https://toml.io). A scene consists of the
+//! view plane, a camera, lights, named materials, and the objects in the
+//! world. For example:
+//!
+//! ```toml
+//! background = [0.7, 0.7, 1.0]
+//!
+//! [view]
+//! width = 400
+//! height = 300
+//! pixel_size = 0.1
+//! sampler = { type = "multi-jittered", samples = 16 }
+//!
+//! [camera]
+//! type = "pinhole"
+//! eye = [0.0, 5.0, 50.0]
+//! centre = [0.0, 5.0, 0.0]
+//! view_distance = 40.0
+//!
+//! [ambient]
+//! intensity = 1.0
+//!
+//! [[lights]]
+//! type = "point"
+//! position = [-50.0, 50.0, 0.0]
+//! intensity = 18000.0
+//!
+//! [materials.white]
+//! type = "matte"
+//! ambient = 0.25
+//! diffuse = 0.65
+//! colour = [1.0, 1.0, 1.0]
+//!
+//! [[objects]]
+//! type = "sphere"
+//! centre = [0.0, 4.0, 0.0]
+//! radius = 4.0
+//! material = "white"
+//!
+//! [[objects]]
+//! type = "mesh"
+//! path = "teapot.obj"
+//! ```
+//!
+//! The available types of each component are:
+//!
+//! - samplers: `random`, `jittered`, `regular`, `n-rooks`, `multi-jittered`,
+//!   and `hammersley`
+//! - cameras: `pinhole`, `orthographic`, `thin-lens`, `stereo`, `fisheye`,
+//!   and `spherical`
+//! - lights: `point`, `spot`, `directional`, and the area lights
+//!   `rectangle`, `disc`, and `sphere`
+//! - materials: `matte`, `phong`, `reflective`, `glossy-reflector`,
+//!   `dielectric`, and `emissive`
+//! - objects: `sphere`, `plane`, `cuboid`, `triangle`, `disc`, `annulus`,
+//!   `rectangle`, `cylinder`, `cone`, `torus`, `mesh`, `instance`, and the
+//!   combinations `union`, `intersection`, and `difference`
+//!
+//! Objects with an `emissive` material glow, and are also used as lights to
+//! illuminate the rest of the world, except for planes and tori.
+//!
+//! Cylinders, cones, and tori stand upright around the `y` axis; to move or
+//! turn them, place them with an `instance`. Cylinders and cones are capped,
+//! unless `open = true`.
+//!
+//! An `instance` places another `object` in the world after applying a list
+//! of `transform` steps in order, like `{ scale = [1.0, 2.0, 1.0] }`,
+//! `{ rotate_y = 45.0 }` (in degrees), `{ rotate = { axis = [1.0, 1.0, 0.0],
+//! angle = 30.0 } }`, `{ translate = [0.0, 1.0, 0.0] }`, or `{ look_at = {
+//! eye = [..], centre = [..] } }`.
+//!
+//! Combinations join a list of `objects` into a single solid, using
+//! [constructive solid geometry](crate::csg): a `difference` cuts every other
+//! object out of the first. The objects should be closed solids, so flat
+//! shapes and open cylinders and cones don't work.
+//!
+//! Meshes are loaded from [OBJ files](crate::obj), relative to the directory
+//! of the scene file. They use the materials from their MTL libraries, unless
+//! a `material` is given to override them.
+//!
+//! The `[view]` table also controls how the image is converted for display:
+//! `exposure` in stops, a `tone_map` (`clamp`, `reinhard`,
+//! `reinhard-extended` with a `white` point, `hable`, or `aces`), and the
+//! display `gamma`. Without a `gamma` colours are encoded with the sRGB
+//! transfer function, rather than a plain power curve. See
+//! [`tonemap`](crate::tonemap) for details.
+//!
+//! The `[ambient]` light can also be blocked by nearby objects, darkening
+//! creases and corners, by setting `occlusion = true`. Only objects within its
+//! `max_distance` occlude it, if one is given, and its directions come from the
+//! view's `sampler` unless it has its own.
+//!
+//! An `[environment]` table surrounds the world with an equirectangular image,
+//! loaded relative to the scene file like meshes. It replaces the background
+//! colour, and also lights the world. It can be turned with a `rotation` in
+//! degrees, and brightened with an `intensity`.
+//!
+//! A `stereo` camera renders a pair of pinhole views, `separation` apart, for
+//! 3D displays. Its `convergence` is `parallel`, `toe-in`, or `off-axis`, and
+//! its `layout` is `side-by-side`, `over-under`, or a red/cyan `anaglyph`.
+//!
+//! Alternative cameras may be given names in a `[cameras]` table, like
+//! `[cameras.closeup]`, and then chosen when loading using [`Overrides`]. If
+//! there is no default `[camera]`, and only one named camera, then that is
+//! used instead.
+//!
+//! See the fields of the description types in this module's source for the
+//! full set of options for each component.
+
+use crate::bvh::Bvh;
+use crate::camera::{self, Camera};
+use crate::csg::{Csg, Operation};
+use crate::environment::EnvironmentMap;
+use crate::film::{self, Film};
+use crate::geometry::{self, Geometry};
+use crate::light::{self, EnvironmentLight, GeometryLight, Light};
+use crate::material::{
+    Dielectric, Emissive, GlossyReflector, Material, Matte, Phong, Reflective,
+};
+use crate::mesh::TriangleMesh;
+use crate::obj;
+use crate::sampler::{self, Generator};
+use crate::tonemap::{Display, Gamma, ToneMap};
+use crate::utility::{Colour, Transform, Vec3};
+use crate::world::{ViewPlane, World};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Errors that can occur while loading a scene.
+#[derive(Debug)]
+pub enum Error {
+    /// The scene file could not be read.
+    Io { path: PathBuf, source: io::Error },
+    /// The scene file was malformed or invalid.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// The scene couldn't be built as requested, for reasons not attributable
+    /// to any particular line of the file.
+    Invalid { path: PathBuf, message: String },
+    /// A model used in the scene could not be loaded.
+    Model(obj::Error),
+    /// An image used in the scene could not be loaded.
+    Image(film::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Invalid { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            },
+            Error::Model(err) => err.fmt(f),
+            Error::Image(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { .. } | Error::Invalid { .. } => None,
+            Error::Model(err) => Some(err),
+            Error::Image(err) => Some(err),
+        }
+    }
+}
+
+impl From<obj::Error> for Error {
+    fn from(err: obj::Error) -> Self {
+        Error::Model(err)
+    }
+}
+
+impl From<film::Error> for Error {
+    fn from(err: film::Error) -> Self {
+        Error::Image(err)
+    }
+}
+
+/// Settings to change from those given in a scene file.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// The resolution of the image, as `(width, height)`.
+    ///
+    /// The size of each pixel is scaled to keep the same horizontal field of
+    /// view.
+    pub resolution: Option<(u32, u32)>,
+    /// The number of samples to take per pixel.
+    pub samples: Option<usize>,
+    /// The name of the camera to use, from the scene's `[cameras]` table.
+    pub camera: Option<String>,
+}
+
+/// A scene ready to be rendered.
+#[derive(Debug)]
+pub struct Scene {
+    pub world: World,
+    pub camera: Box<dyn Camera>,
+}
+
+impl Scene {
+    /// Loads a scene from the file at `path`, applying any `overrides`.
+    pub fn load(path: impl AsRef<Path>, overrides: &Overrides) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|source| Error::Io {
+            path: path.to_owned(),
+            source,
+        })?;
+        Self::parse(&source, path, overrides)
+    }
+
+    /// Parses a scene from the contents of a scene file.
+    ///
+    /// `path` is used for error messages and to find any models used by the
+    /// scene; it doesn't need to exist otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raytracer::scene::{Overrides, Scene};
+    /// use std::path::Path;
+    ///
+    /// let source = r#"
+    ///     [view]
+    ///     width = 40
+    ///     height = 30
+    ///
+    ///     [camera]
+    ///     type = "fisheye"
+    ///     eye = [0.0, 0.0, 10.0]
+    ///     centre = [0.0, 0.0, 0.0]
+    ///     fov = 180.0
+    ///
+    ///     [[objects]]
+    ///     type = "sphere"
+    ///     centre = [0.0, 0.0, 0.0]
+    ///     radius = 1.0
+    ///     material = "missing"
+    /// "#;
+    ///
+    /// let overrides = Overrides::default();
+    /// let err = Scene::parse(source, Path::new("test.toml"), &overrides).unwrap_err();
+    /// assert_eq!(err.to_string(), "test.toml:12: unknown material `missing`");
+    /// ```
+    pub fn parse(
+        source: &str,
+        path: &Path,
+        overrides: &Overrides,
+    ) -> Result<Self, Error> {
+        let loader = Loader {
+            source,
+            path,
+            overrides,
+        };
+        let desc: SceneDesc = toml::from_str(source).map_err(|err| {
+            let line = err.span().map_or(0, |span| loader.line(span.start));
+            loader.error(line, err.message())
+        })?;
+        loader.build(desc)
+    }
+}
+
+/// A vector or colour in a scene file.
+type Triple = [f64; 3];
+
+fn vec3([x, y, z]: Triple) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+fn colour([r, g, b]: Triple) -> Colour {
+    Colour::new(r, g, b)
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+fn inverse_square() -> f64 {
+    light::INVERSE_SQUARE
+}
+
+fn white() -> Triple {
+    [1.0, 1.0, 1.0]
+}
+
+fn black() -> Triple {
+    [0.0, 0.0, 0.0]
+}
+
+fn up() -> Triple {
+    [0.0, 1.0, 0.0]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default = "black")]
+    background: Triple,
+    view: Spanned<ViewDesc>,
+    camera: Option<Spanned<CameraDesc>>,
+    #[serde(default)]
+    cameras: HashMap<String, Spanned<CameraDesc>>,
+    ambient: Option<Spanned<AmbientDesc>>,
+    environment: Option<Spanned<EnvironmentDesc>>,
+    #[serde(default)]
+    lights: Vec<Spanned<LightDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    objects: Vec<Spanned<ObjectDesc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ViewDesc {
+    width: u32,
+    height: u32,
+    #[serde(default = "one")]
+    pixel_size: f64,
+    gamma: Option<f64>,
+    #[serde(default)]
+    exposure: f64,
+    #[serde(default)]
+    tone_map: ToneMapKind,
+    white: Option<f64>,
+    #[serde(default)]
+    sampler: SamplerDesc,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ToneMapKind {
+    #[default]
+    Clamp,
+    Reinhard,
+    ReinhardExtended,
+    Hable,
+    Aces,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SamplerDesc {
+    #[serde(rename = "type")]
+    kind: SamplerKind,
+    samples: usize,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            kind: SamplerKind::MultiJittered,
+            samples: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum SamplerKind {
+    Random,
+    Jittered,
+    Regular,
+    NRooks,
+    MultiJittered,
+    Hammersley,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum CameraDesc {
+    Pinhole {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        view_distance: f64,
+        #[serde(default = "one")]
+        zoom: f64,
+    },
+    Orthographic {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        /// Width of the view, in world units.
+        width: f64,
+    },
+    ThinLens {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        view_distance: f64,
+        focal_distance: f64,
+        lens_radius: f64,
+        #[serde(default = "one")]
+        zoom: f64,
+        /// Sampler for the lens; defaults to the same as the view plane.
+        sampler: Option<SamplerDesc>,
+    },
+    Stereo {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        view_distance: f64,
+        #[serde(default = "one")]
+        zoom: f64,
+        /// Distance between the eyes.
+        separation: f64,
+        #[serde(default)]
+        convergence: ConvergenceKind,
+        #[serde(default)]
+        layout: StereoLayoutKind,
+    },
+    Fisheye {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        /// Field of view, in degrees.
+        fov: f64,
+    },
+    Spherical {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+        /// Horizontal field of view, in degrees.
+        azimuth: f64,
+        /// Vertical field of view, in degrees.
+        polar: f64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ConvergenceKind {
+    Parallel,
+    ToeIn,
+    #[default]
+    OffAxis,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum StereoLayoutKind {
+    #[default]
+    SideBySide,
+    OverUnder,
+    Anaglyph,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    /// Path to an equirectangular image, relative to the scene file.
+    path: PathBuf,
+    /// Rotation about the vertical axis, in degrees.
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default = "one")]
+    intensity: f64,
+    /// Sampler for directions to the environment; defaults to the same as the
+    /// view plane.
+    sampler: Option<SamplerDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AmbientDesc {
+    #[serde(default = "one")]
+    intensity: f64,
+    #[serde(default = "white")]
+    colour: Triple,
+    /// Whether nearby objects block the ambient light.
+    #[serde(default)]
+    occlusion: bool,
+    /// How far away objects can be and still occlude; defaults to any
+    /// distance.
+    max_distance: Option<f64>,
+    /// Sampler for occlusion directions; defaults to the same as the view
+    /// plane.
+    sampler: Option<SamplerDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum LightDesc {
+    Point {
+        position: Triple,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        #[serde(default = "inverse_square")]
+        falloff: f64,
+    },
+    Spot {
+        position: Triple,
+        direction: Triple,
+        /// Angle from the axis of full brightness, in degrees.
+        #[serde(default)]
+        inner: f64,
+        /// Angle from the axis where the light ends, in degrees.
+        outer: f64,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        #[serde(default = "inverse_square")]
+        falloff: f64,
+    },
+    Directional {
+        direction: Triple,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+    },
+    Rectangle {
+        corner: Triple,
+        a: Triple,
+        b: Triple,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        /// Sampler for points on the light; defaults to the same as the view
+        /// plane.
+        sampler: Option<SamplerDesc>,
+    },
+    Disc {
+        centre: Triple,
+        normal: Triple,
+        radius: f64,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        sampler: Option<SamplerDesc>,
+    },
+    Sphere {
+        centre: Triple,
+        radius: f64,
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        sampler: Option<SamplerDesc>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum MaterialDesc {
+    Matte {
+        ambient: f64,
+        diffuse: f64,
+        colour: Triple,
+    },
+    Phong {
+        ambient: f64,
+        diffuse: f64,
+        specular: f64,
+        shininess: f64,
+        colour: Triple,
+    },
+    Reflective {
+        ambient: f64,
+        diffuse: f64,
+        specular: f64,
+        shininess: f64,
+        colour: Triple,
+        reflection: f64,
+        #[serde(default = "white")]
+        reflection_colour: Triple,
+    },
+    GlossyReflector {
+        ambient: f64,
+        diffuse: f64,
+        specular: f64,
+        shininess: f64,
+        colour: Triple,
+        reflection: f64,
+        glossiness: f64,
+        #[serde(default = "white")]
+        reflection_colour: Triple,
+    },
+    Dielectric {
+        #[serde(default)]
+        ambient: f64,
+        #[serde(default)]
+        diffuse: f64,
+        #[serde(default)]
+        specular: f64,
+        #[serde(default = "one")]
+        shininess: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+        ior: f64,
+        #[serde(default = "one")]
+        outside_ior: f64,
+        #[serde(default = "white")]
+        filter: Triple,
+        #[serde(default = "white")]
+        outside_filter: Triple,
+    },
+    Emissive {
+        #[serde(default = "one")]
+        intensity: f64,
+        #[serde(default = "white")]
+        colour: Triple,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        centre: Triple,
+        radius: f64,
+        material: String,
+    },
+    Plane {
+        point: Triple,
+        normal: Triple,
+        material: String,
+    },
+    Cuboid {
+        min: Triple,
+        max: Triple,
+        material: String,
+    },
+    Triangle {
+        a: Triple,
+        b: Triple,
+        c: Triple,
+        material: String,
+    },
+    Disc {
+        centre: Triple,
+        normal: Triple,
+        radius: f64,
+        material: String,
+    },
+    Annulus {
+        centre: Triple,
+        normal: Triple,
+        inner_radius: f64,
+        outer_radius: f64,
+        material: String,
+    },
+    Rectangle {
+        corner: Triple,
+        a: Triple,
+        b: Triple,
+        material: String,
+    },
+    Cylinder {
+        radius: f64,
+        bottom: f64,
+        top: f64,
+        #[serde(default)]
+        open: bool,
+        material: String,
+    },
+    Cone {
+        radius: f64,
+        height: f64,
+        #[serde(default)]
+        open: bool,
+        material: String,
+    },
+    Torus {
+        swept_radius: f64,
+        tube_radius: f64,
+        material: String,
+    },
+    Mesh {
+        path: PathBuf,
+        material: Option<String>,
+    },
+    Union {
+        objects: Vec<ObjectDesc>,
+    },
+    Intersection {
+        objects: Vec<ObjectDesc>,
+    },
+    Difference {
+        objects: Vec<ObjectDesc>,
+    },
+    Instance {
+        object: Box<ObjectDesc>,
+        /// Applied in order, starting from the object's own space.
+        transform: Vec<TransformDesc>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum TransformDesc {
+    Translate(Triple),
+    Scale(Triple),
+    /// Angles are in degrees.
+    RotateX(f64),
+    RotateY(f64),
+    RotateZ(f64),
+    Rotate {
+        axis: Triple,
+        angle: f64,
+    },
+    LookAt {
+        eye: Triple,
+        centre: Triple,
+        #[serde(default = "up")]
+        up: Triple,
+    },
+}
+
+/// Ensures that `n` is a valid number of samples for the given kind of
+/// sampler.
+fn check_samples(kind: SamplerKind, n: usize) -> Result<(), String> {
+    let square = [
+        SamplerKind::Jittered,
+        SamplerKind::Regular,
+        SamplerKind::MultiJittered,
+    ];
+    let root = (n as f64).sqrt() as usize;
+
+    if n == 0 {
+        Err("must have at least one sample".to_string())
+    } else if square.contains(&kind) && root * root != n {
+        Err(format!(
+            "number of samples must be a perfect square, not {}",
+            n
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Converts scene descriptions into an actual scene.
+struct Loader<'a> {
+    source: &'a str,
+    path: &'a Path,
+    overrides: &'a Overrides,
+}
+
+impl Loader<'_> {
+    fn error(&self, line: usize, message: impl Into<String>) -> Error {
+        Error::Parse {
+            path: self.path.to_owned(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// The line number of the given byte offset in the source.
+    fn line(&self, offset: usize) -> usize {
+        self.source[..offset].matches('\n').count() + 1
+    }
+
+    /// Resolves a path relative to the directory of the scene file.
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        dir.join(path)
+    }
+
+    /// Creates an error at the start of the given item.
+    fn invalid<T>(&self, item: &Spanned<T>, message: impl Into<String>) -> Error {
+        self.error(self.line(item.span().start), message)
+    }
+
+    /// Creates an error that doesn't relate to any particular line.
+    fn invalid_scene(&self, message: impl Into<String>) -> Error {
+        Error::Invalid {
+            path: self.path.to_owned(),
+            message: message.into(),
+        }
+    }
+
+    fn build(&self, desc: SceneDesc) -> Result<Scene, Error> {
+        let camera_desc = self.select_camera(&desc)?;
+        let mut view_sampler = desc.view.get_ref().sampler;
+        if let Some(samples) = self.overrides.samples {
+            let mut kinds = vec![view_sampler.kind];
+            if let CameraDesc::ThinLens {
+                sampler: Some(sampler),
+                ..
+            } = camera_desc.get_ref()
+            {
+                kinds.push(sampler.kind);
+            }
+            for kind in kinds {
+                check_samples(kind, samples).map_err(|msg| self.invalid_scene(msg))?;
+            }
+            view_sampler.samples = samples;
+        }
+
+        let view = self.view(&desc.view, &view_sampler)?;
+        let camera = self.camera(camera_desc, &view_sampler)?;
+
+        let ambient = match &desc.ambient {
+            Some(ambient) => self.ambient(ambient, &view_sampler)?,
+            None => Box::new(light::Ambient::new(1.0)),
+        };
+
+        let mut lights: Vec<Box<dyn Light>> = desc
+            .lights
+            .iter()
+            .map(|l| self.light(l, &view_sampler))
+            .collect::<Result<_, _>>()?;
+
+        let materials = desc
+            .materials
+            .iter()
+            .map(|(name, m)| Ok((name.as_str(), self.material(m)?)))
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        let mut objects = Vec::new();
+        for object in &desc.objects {
+            self.object(object, object.get_ref(), &materials, &mut objects)?;
+        }
+
+        // glowing objects light the rest of the world too
+        let light_sampler = self.sampler(&desc.view, &view_sampler)?;
+        let objects = objects
+            .into_iter()
+            .map(|object| -> Box<dyn Geometry> {
+                if object.material().emission().max_channel() <= 0.0 {
+                    return object;
+                }
+                let object = Arc::<dyn Geometry>::from(object);
+                if let Some(light) =
+                    GeometryLight::new(Arc::clone(&object), &light_sampler)
+                {
+                    lights.push(Box::new(light));
+                }
+                Box::new(object)
+            })
+            .collect();
+
+        let environment = match &desc.environment {
+            Some(env) => {
+                let EnvironmentDesc {
+                    path,
+                    rotation,
+                    intensity,
+                    sampler,
+                } = env.get_ref();
+                self.non_negative(env, &[("intensity", *intensity)])?;
+                let film = Film::load(self.relative_path(path))?;
+                if film.width() == 0 || film.height() == 0 {
+                    return Err(self.invalid(env, "environment map must not be empty"));
+                }
+                let map = Arc::new(EnvironmentMap::new(&film, *rotation, *intensity));
+                let sampler =
+                    self.sampler(env, sampler.as_ref().unwrap_or(&view_sampler))?;
+                lights.push(Box::new(EnvironmentLight::new(Arc::clone(&map), &sampler)));
+                Some(map)
+            },
+            None => None,
+        };
+
+        let world = World {
+            background: colour(desc.background),
+            environment,
+            view,
+            objects: Bvh::new(objects),
+            ambient,
+            lights,
+        };
+
+        Ok(Scene { world, camera })
+    }
+
+    /// Chooses which camera to use, either from the overrides or the default.
+    fn select_camera<'d>(
+        &self,
+        desc: &'d SceneDesc,
+    ) -> Result<&'d Spanned<CameraDesc>, Error> {
+        if let Some(name) = &self.overrides.camera {
+            return desc.cameras.get(name).ok_or_else(|| {
+                let mut names =
+                    desc.cameras.keys().map(String::as_str).collect::<Vec<_>>();
+                names.sort_unstable();
+                let message = format!(
+                    "no camera named `{}` (available cameras: {})",
+                    name,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    },
+                );
+                self.invalid_scene(message)
+            });
+        }
+
+        match &desc.camera {
+            Some(camera) => Ok(camera),
+            None if desc.cameras.len() == 1 => Ok(desc.cameras.values().next().unwrap()),
+            None if desc.cameras.is_empty() => {
+                Err(self.invalid_scene("missing `[camera]`"))
+            },
+            None => {
+                Err(self.invalid_scene("no default `[camera]`, so one must be chosen"))
+            },
+        }
+    }
+
+    fn view(
+        &self,
+        desc: &Spanned<ViewDesc>,
+        sampler: &SamplerDesc,
+    ) -> Result<ViewPlane, Error> {
+        let view = desc.get_ref();
+        if view.width == 0 || view.height == 0 {
+            return Err(self.invalid(desc, "image size must be non-zero"));
+        }
+        if view.pixel_size <= 0.0 {
+            return Err(self.invalid(desc, "pixel size must be positive"));
+        }
+        let gamma = match view.gamma {
+            None => Gamma::Srgb,
+            Some(gamma) if gamma > 0.0 => Gamma::Power(gamma),
+            Some(_) => return Err(self.invalid(desc, "gamma must be positive")),
+        };
+        let tone_map = match (view.tone_map, view.white) {
+            (ToneMapKind::ReinhardExtended, Some(white)) if white > 0.0 => {
+                ToneMap::ReinhardExtended { white }
+            },
+            (ToneMapKind::ReinhardExtended, Some(_)) => {
+                return Err(self.invalid(desc, "white point must be positive"));
+            },
+            (ToneMapKind::ReinhardExtended, None) => {
+                return Err(
+                    self.invalid(desc, "`reinhard-extended` needs a `white` point")
+                );
+            },
+            (_, Some(_)) => {
+                let message = "`white` only applies to `reinhard-extended` tone mapping";
+                return Err(self.invalid(desc, message));
+            },
+            (ToneMapKind::Clamp, None) => ToneMap::Clamp,
+            (ToneMapKind::Reinhard, None) => ToneMap::Reinhard,
+            (ToneMapKind::Hable, None) => ToneMap::Hable,
+            (ToneMapKind::Aces, None) => ToneMap::Aces,
+        };
+
+        let (hres, vres, s) = match self.overrides.resolution {
+            Some((0, _)) | Some((_, 0)) => {
+                return Err(self.invalid_scene("image size must be non-zero"));
+            },
+            Some((width, height)) => {
+                let s = view.pixel_size * f64::from(view.width) / f64::from(width);
+                (width, height, s)
+            },
+            None => (view.width, view.height, view.pixel_size),
+        };
+
+        Ok(ViewPlane {
+            hres,
+            vres,
+            s,
+            display: Display {
+                tone_map,
+                exposure: view.exposure,
+                gamma,
+            },
+            sampler: self.sampler(desc, sampler)?,
+        })
+    }
+
+    fn sampler<T>(
+        &self,
+        item: &Spanned<T>,
+        desc: &SamplerDesc,
+    ) -> Result<Box<dyn Generator>, Error> {
+        let n = desc.samples;
+        check_samples(desc.kind, n).map_err(|msg| self.invalid(item, msg))?;
+
+        Ok(match desc.kind {
+            SamplerKind::Random => Box::new(sampler::Random::new(n)),
+            SamplerKind::Jittered => Box::new(sampler::Jittered::new(n)),
+            SamplerKind::Regular => Box::new(sampler::Regular::new(n)),
+            SamplerKind::NRooks => Box::new(sampler::NRooks::new(n)),
+            SamplerKind::MultiJittered => Box::new(sampler::MultiJittered::new(n)),
+            SamplerKind::Hammersley => Box::new(sampler::Hammersley::new(n)),
+        })
+    }
+
+    fn location(
+        &self,
+        item: &Spanned<CameraDesc>,
+        eye: Triple,
+        centre: Triple,
+        up: Triple,
+    ) -> Result<camera::Location, Error> {
+        let location = camera::Location {
+            eye: vec3(eye),
+            centre: vec3(centre),
+            up: vec3(up),
+        };
+
+        let forward = location.centre - location.eye;
+        if forward.mag() == 0.0 {
+            return Err(self.invalid(item, "camera must not look at its own eye"));
+        }
+        if forward.cross(location.up).mag() == 0.0 {
+            return Err(self.invalid(
+                item,
+                "camera's up direction must not be parallel to its view",
+            ));
+        }
+
+        Ok(location)
+    }
+
+    /// Checks the view plane distance and zoom of a perspective camera.
+    fn perspective(
+        &self,
+        item: &Spanned<CameraDesc>,
+        view_distance: f64,
+        zoom: f64,
+    ) -> Result<(), Error> {
+        if view_distance <= 0.0 {
+            return Err(self.invalid(item, "view distance must be positive"));
+        }
+        if zoom <= 0.0 {
+            return Err(self.invalid(item, "zoom must be positive"));
+        }
+        Ok(())
+    }
+
+    /// Checks that each of the named `values` isn't negative.
+    fn non_negative<T>(
+        &self,
+        item: &Spanned<T>,
+        values: &[(&str, f64)],
+    ) -> Result<(), Error> {
+        match values.iter().find(|(_, value)| *value < 0.0) {
+            Some((name, _)) => {
+                Err(self.invalid(item, format!("{} must not be negative", name)))
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn camera(
+        &self,
+        desc: &Spanned<CameraDesc>,
+        view_sampler: &SamplerDesc,
+    ) -> Result<Box<dyn Camera>, Error> {
+        Ok(match *desc.get_ref() {
+            CameraDesc::Pinhole {
+                eye,
+                centre,
+                up,
+                view_distance,
+                zoom,
+            } => {
+                self.perspective(desc, view_distance, zoom)?;
+                let location = self.location(desc, eye, centre, up)?;
+                Box::new(camera::Pinhole::new(location, view_distance, zoom))
+            },
+            CameraDesc::Orthographic {
+                eye,
+                centre,
+                up,
+                width,
+            } => {
+                if width <= 0.0 {
+                    return Err(self.invalid(desc, "width must be positive"));
+                }
+                let location = self.location(desc, eye, centre, up)?;
+                Box::new(camera::Orthographic::new(location, width))
+            },
+            CameraDesc::ThinLens {
+                eye,
+                centre,
+                up,
+                view_distance,
+                focal_distance,
+                lens_radius,
+                zoom,
+                sampler,
+            } => {
+                self.perspective(desc, view_distance, zoom)?;
+                if focal_distance <= 0.0 {
+                    return Err(self.invalid(desc, "focal distance must be positive"));
+                }
+                if lens_radius < 0.0 {
+                    return Err(self.invalid(desc, "lens radius must not be negative"));
+                }
+                let location = self.location(desc, eye, centre, up)?;
+                let mut sampler = sampler.unwrap_or(*view_sampler);
+                if let Some(samples) = self.overrides.samples {
+                    sampler.samples = samples;
+                }
+                if sampler.samples != view_sampler.samples {
+                    let message = "lens must use the same number of samples as the view";
+                    return Err(self.invalid(desc, message));
+                }
+
+                Box::new(camera::ThinLens::new(
+                    location,
+                    view_distance,
+                    focal_distance,
+                    lens_radius,
+                    zoom,
+                    self.sampler(desc, &sampler)?,
+                ))
+            },
+            CameraDesc::Stereo {
+                eye,
+                centre,
+                up,
+                view_distance,
+                zoom,
+                separation,
+                convergence,
+                layout,
+            } => {
+                self.perspective(desc, view_distance, zoom)?;
+                if separation < 0.0 {
+                    return Err(self.invalid(desc, "separation must not be negative"));
+                }
+                let location = self.location(desc, eye, centre, up)?;
+                let convergence = match convergence {
+                    ConvergenceKind::Parallel => camera::Convergence::Parallel,
+                    ConvergenceKind::ToeIn => camera::Convergence::ToeIn,
+                    ConvergenceKind::OffAxis => camera::Convergence::OffAxis,
+                };
+                let layout = match layout {
+                    StereoLayoutKind::SideBySide => camera::StereoLayout::SideBySide,
+                    StereoLayoutKind::OverUnder => camera::StereoLayout::OverUnder,
+                    StereoLayoutKind::Anaglyph => camera::StereoLayout::Anaglyph,
+                };
+                Box::new(camera::Stereo::pinhole(
+                    location,
+                    view_distance,
+                    zoom,
+                    separation,
+                    convergence,
+                    layout,
+                ))
+            },
+            CameraDesc::Fisheye {
+                eye,
+                centre,
+                up,
+                fov,
+            } => {
+                if !(fov > 0.0 && fov <= 360.0) {
+                    let message = "field of view must be in (0, 360] degrees";
+                    return Err(self.invalid(desc, message));
+                }
+                let location = self.location(desc, eye, centre, up)?;
+                Box::new(camera::Fisheye::new(location, fov))
+            },
+            CameraDesc::Spherical {
+                eye,
+                centre,
+                up,
+                azimuth,
+                polar,
+            } => {
+                let location = self.location(desc, eye, centre, up)?;
+                Box::new(camera::Spherical::new(location, azimuth, polar))
+            },
+        })
+    }
+
+    fn ambient(
+        &self,
+        desc: &Spanned<AmbientDesc>,
+        view_sampler: &SamplerDesc,
+    ) -> Result<Box<dyn Light>, Error> {
+        let AmbientDesc {
+            intensity,
+            colour,
+            occlusion,
+            max_distance,
+            ref sampler,
+        } = *desc.get_ref();
+        self.non_negative(desc, &[("intensity", intensity)])?;
+        let colour = self::colour(colour);
+        if !occlusion {
+            if max_distance.is_some() || sampler.is_some() {
+                let message = "max_distance and sampler require occlusion = true";
+                return Err(self.invalid(desc, message));
+            }
+            return Ok(Box::new(light::Ambient::with_colour(intensity, colour)));
+        }
+
+        let max_distance = max_distance.unwrap_or(f64::INFINITY);
+        if max_distance <= 0.0 {
+            return Err(self.invalid(desc, "max distance must be positive"));
+        }
+        let sampler = self.sampler(desc, sampler.as_ref().unwrap_or(view_sampler))?;
+        Ok(Box::new(light::AmbientOccluder::with_colour(
+            intensity,
+            colour,
+            max_distance,
+            &sampler,
+        )))
+    }
+
+    fn light(
+        &self,
+        desc: &Spanned<LightDesc>,
+        view_sampler: &SamplerDesc,
+    ) -> Result<Box<dyn Light>, Error> {
+        let intensity = match *desc.get_ref() {
+            LightDesc::Point { intensity, .. }
+            | LightDesc::Spot { intensity, .. }
+            | LightDesc::Directional { intensity, .. }
+            | LightDesc::Rectangle { intensity, .. }
+            | LightDesc::Disc { intensity, .. }
+            | LightDesc::Sphere { intensity, .. } => intensity,
+        };
+        self.non_negative(desc, &[("intensity", intensity)])?;
+
+        Ok(match *desc.get_ref() {
+            LightDesc::Point {
+                position,
+                intensity,
+                colour,
+                falloff,
+            } => {
+                if falloff < 0.0 {
+                    return Err(self.invalid(desc, "falloff must not be negative"));
+                }
+                let mut light = light::PointLight::with_colour(
+                    intensity,
+                    vec3(position),
+                    self::colour(colour),
+                );
+                light.falloff = falloff;
+                Box::new(light)
+            },
+            LightDesc::Spot {
+                position,
+                direction,
+                inner,
+                outer,
+                intensity,
+                colour,
+                falloff,
+            } => {
+                let direction = vec3(direction);
+                if direction.mag() == 0.0 {
+                    return Err(self.invalid(desc, "direction must be non-zero"));
+                }
+                if !(0.0 <= inner && inner <= outer && outer <= 180.0) {
+                    let message = "cone angles must satisfy 0 <= inner <= outer <= 180";
+                    return Err(self.invalid(desc, message));
+                }
+                if falloff < 0.0 {
+                    return Err(self.invalid(desc, "falloff must not be negative"));
+                }
+                let mut light = light::SpotLight::with_colour(
+                    intensity,
+                    vec3(position),
+                    direction,
+                    inner,
+                    outer,
+                    self::colour(colour),
+                );
+                light.falloff = falloff;
+                Box::new(light)
+            },
+            LightDesc::Directional {
+                direction,
+                intensity,
+                colour,
+            } => {
+                let direction = vec3(direction);
+                if direction.mag() == 0.0 {
+                    return Err(self.invalid(desc, "direction must be non-zero"));
+                }
+                Box::new(light::Directional::with_colour(
+                    intensity,
+                    direction,
+                    self::colour(colour),
+                ))
+            },
+            LightDesc::Rectangle {
+                corner,
+                a,
+                b,
+                intensity,
+                colour,
+                ref sampler,
+            } => {
+                let (a, b) = (vec3(a), vec3(b));
+                if a.cross(b).mag() == 0.0 {
+                    return Err(
+                        self.invalid(desc, "rectangle sides must not be parallel")
+                    );
+                }
+                let shape = light::Shape::Rectangle {
+                    corner: vec3(corner),
+                    a,
+                    b,
+                };
+                let sampler =
+                    self.sampler(desc, sampler.as_ref().unwrap_or(view_sampler))?;
+                Box::new(light::AreaLight::new(
+                    shape,
+                    intensity,
+                    self::colour(colour),
+                    &sampler,
+                ))
+            },
+            LightDesc::Disc {
+                centre,
+                normal,
+                radius,
+                intensity,
+                colour,
+                ref sampler,
+            } => {
+                let normal = vec3(normal);
+                if normal.mag() == 0.0 {
+                    return Err(self.invalid(desc, "normal must be non-zero"));
+                }
+                if radius <= 0.0 {
+                    return Err(self.invalid(desc, "radius must be positive"));
+                }
+                let shape = light::Shape::Disc {
+                    centre: vec3(centre),
+                    normal: normal.normalise(),
+                    radius,
+                };
+                let sampler =
+                    self.sampler(desc, sampler.as_ref().unwrap_or(view_sampler))?;
+                Box::new(light::AreaLight::new(
+                    shape,
+                    intensity,
+                    self::colour(colour),
+                    &sampler,
+                ))
+            },
+            LightDesc::Sphere {
+                centre,
+                radius,
+                intensity,
+                colour,
+                ref sampler,
+            } => {
+                if radius <= 0.0 {
+                    return Err(self.invalid(desc, "radius must be positive"));
+                }
+                let shape = light::Shape::Sphere {
+                    centre: vec3(centre),
+                    radius,
+                };
+                let sampler =
+                    self.sampler(desc, sampler.as_ref().unwrap_or(view_sampler))?;
+                Box::new(light::AreaLight::new(
+                    shape,
+                    intensity,
+                    self::colour(colour),
+                    &sampler,
+                ))
+            },
+        })
+    }
+
+    fn material(&self, desc: &Spanned<MaterialDesc>) -> Result<Arc<dyn Material>, Error> {
+        Ok(match *desc.get_ref() {
+            MaterialDesc::Matte {
+                ambient,
+                diffuse,
+                colour,
+            } => {
+                self.non_negative(desc, &[("ambient", ambient), ("diffuse", diffuse)])?;
+                Arc::new(Matte::new(ambient, diffuse, self::colour(colour)))
+            },
+            MaterialDesc::Phong {
+                ambient,
+                diffuse,
+                specular,
+                shininess,
+                colour,
+            } => {
+                self.phong(desc, ambient, diffuse, specular, shininess)?;
+                Arc::new(Phong::new(
+                    ambient,
+                    diffuse,
+                    specular,
+                    shininess,
+                    self::colour(colour),
+                ))
+            },
+            MaterialDesc::Reflective {
+                ambient,
+                diffuse,
+                specular,
+                shininess,
+                colour,
+                reflection,
+                reflection_colour,
+            } => {
+                self.phong(desc, ambient, diffuse, specular, shininess)?;
+                self.non_negative(desc, &[("reflection", reflection)])?;
+                let phong = Phong::new(
+                    ambient,
+                    diffuse,
+                    specular,
+                    shininess,
+                    self::colour(colour),
+                );
+                Arc::new(Reflective::new(
+                    phong,
+                    reflection,
+                    self::colour(reflection_colour),
+                ))
+            },
+            MaterialDesc::GlossyReflector {
+                ambient,
+                diffuse,
+                specular,
+                shininess,
+                colour,
+                reflection,
+                glossiness,
+                reflection_colour,
+            } => {
+                self.phong(desc, ambient, diffuse, specular, shininess)?;
+                self.non_negative(
+                    desc,
+                    &[("reflection", reflection), ("glossiness", glossiness)],
+                )?;
+                let phong = Phong::new(
+                    ambient,
+                    diffuse,
+                    specular,
+                    shininess,
+                    self::colour(colour),
+                );
+                Arc::new(GlossyReflector::new(
+                    phong,
+                    reflection,
+                    glossiness,
+                    self::colour(reflection_colour),
+                ))
+            },
+            MaterialDesc::Dielectric {
+                ambient,
+                diffuse,
+                specular,
+                shininess,
+                colour,
+                ior,
+                outside_ior,
+                filter,
+                outside_filter,
+            } => {
+                self.phong(desc, ambient, diffuse, specular, shininess)?;
+                if ior <= 0.0 || outside_ior <= 0.0 {
+                    return Err(
+                        self.invalid(desc, "index of refraction must be positive")
+                    );
+                }
+                let phong = Phong::new(
+                    ambient,
+                    diffuse,
+                    specular,
+                    shininess,
+                    self::colour(colour),
+                );
+                Arc::new(Dielectric::new(
+                    phong,
+                    ior,
+                    outside_ior,
+                    self::colour(filter),
+                    self::colour(outside_filter),
+                ))
+            },
+            MaterialDesc::Emissive { intensity, colour } => {
+                self.non_negative(desc, &[("intensity", intensity)])?;
+                Arc::new(Emissive::new(intensity, self::colour(colour)))
+            },
+        })
+    }
+
+    /// Checks the coefficients shared by materials built on [`Phong`].
+    fn phong(
+        &self,
+        desc: &Spanned<MaterialDesc>,
+        ambient: f64,
+        diffuse: f64,
+        specular: f64,
+        shininess: f64,
+    ) -> Result<(), Error> {
+        self.non_negative(
+            desc,
+            &[
+                ("ambient", ambient),
+                ("diffuse", diffuse),
+                ("specular", specular),
+                ("shininess", shininess),
+            ],
+        )
+    }
+
+    /// Adds the objects described by `desc` to `objects`. Any errors are
+    /// reported at `item`, the entry in the scene containing `desc`.
+    fn object(
+        &self,
+        item: &Spanned<ObjectDesc>,
+        desc: &ObjectDesc,
+        materials: &HashMap<&str, Arc<dyn Material>>,
+        objects: &mut Vec<Box<dyn Geometry>>,
+    ) -> Result<(), Error> {
+        let material = |name: &str| match materials.get(name) {
+            Some(material) => Ok(Arc::clone(material)),
+            None => Err(self.invalid(item, format!("unknown material `{}`", name))),
+        };
+
+        match desc {
+            ObjectDesc::Sphere {
+                centre,
+                radius,
+                material: name,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(self.invalid(item, "sphere radius must be positive"));
+                }
+                objects.push(Box::new(geometry::Sphere {
+                    centre: vec3(*centre),
+                    radius: *radius,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Plane {
+                point,
+                normal,
+                material: name,
+            } => {
+                let normal = vec3(*normal);
+                if normal.mag() == 0.0 {
+                    return Err(self.invalid(item, "plane normal must be non-zero"));
+                }
+                objects.push(Box::new(geometry::Plane {
+                    point: vec3(*point),
+                    normal: normal.normalise(),
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Cuboid {
+                min,
+                max,
+                material: name,
+            } => {
+                let (min, max) = (vec3(*min), vec3(*max));
+                if min.x > max.x || min.y > max.y || min.z > max.z {
+                    let message = "cuboid min must not be greater than max on any axis";
+                    return Err(self.invalid(item, message));
+                }
+                objects.push(Box::new(geometry::Cuboid::new(min, max, material(name)?)));
+            },
+            ObjectDesc::Triangle {
+                a,
+                b,
+                c,
+                material: name,
+            } => {
+                let (a, b, c) = (vec3(*a), vec3(*b), vec3(*c));
+                if (b - a).cross(c - a).mag() == 0.0 {
+                    let message = "triangle vertices must not be collinear";
+                    return Err(self.invalid(item, message));
+                }
+                objects.push(Box::new(geometry::Triangle::new(a, b, c, material(name)?)));
+            },
+            ObjectDesc::Disc {
+                centre,
+                normal,
+                radius,
+                material: name,
+            } => {
+                let normal = vec3(*normal);
+                if normal.mag() == 0.0 {
+                    return Err(self.invalid(item, "disc normal must be non-zero"));
+                }
+                if *radius <= 0.0 {
+                    return Err(self.invalid(item, "disc radius must be positive"));
+                }
+                objects.push(Box::new(geometry::Disc {
+                    centre: vec3(*centre),
+                    normal: normal.normalise(),
+                    radius: *radius,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Annulus {
+                centre,
+                normal,
+                inner_radius,
+                outer_radius,
+                material: name,
+            } => {
+                let normal = vec3(*normal);
+                if normal.mag() == 0.0 {
+                    return Err(self.invalid(item, "annulus normal must be non-zero"));
+                }
+                if *inner_radius < 0.0 || inner_radius >= outer_radius {
+                    let message =
+                        "annulus radii must have 0 <= inner_radius < outer_radius";
+                    return Err(self.invalid(item, message));
+                }
+                objects.push(Box::new(geometry::Annulus {
+                    centre: vec3(*centre),
+                    normal: normal.normalise(),
+                    inner_radius: *inner_radius,
+                    outer_radius: *outer_radius,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Rectangle {
+                corner,
+                a,
+                b,
+                material: name,
+            } => {
+                let (a, b) = (vec3(*a), vec3(*b));
+                if a.cross(b).mag() == 0.0 {
+                    return Err(
+                        self.invalid(item, "rectangle sides must not be parallel")
+                    );
+                }
+                objects.push(Box::new(geometry::Rectangle {
+                    corner: vec3(*corner),
+                    a,
+                    b,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Cylinder {
+                radius,
+                bottom,
+                top,
+                open,
+                material: name,
+            } => {
+                if *radius <= 0.0 {
+                    return Err(self.invalid(item, "cylinder radius must be positive"));
+                }
+                if bottom >= top {
+                    return Err(
+                        self.invalid(item, "cylinder bottom must be below its top")
+                    );
+                }
+                objects.push(Box::new(geometry::Cylinder {
+                    radius: *radius,
+                    bottom: *bottom,
+                    top: *top,
+                    open: *open,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Cone {
+                radius,
+                height,
+                open,
+                material: name,
+            } => {
+                if *radius <= 0.0 || *height <= 0.0 {
+                    let message = "cone radius and height must be positive";
+                    return Err(self.invalid(item, message));
+                }
+                objects.push(Box::new(geometry::Cone {
+                    radius: *radius,
+                    height: *height,
+                    open: *open,
+                    material: material(name)?,
+                }));
+            },
+            ObjectDesc::Torus {
+                swept_radius,
+                tube_radius,
+                material: name,
+            } => {
+                if *swept_radius <= 0.0 || *tube_radius <= 0.0 {
+                    return Err(self.invalid(item, "torus radii must be positive"));
+                }
+                objects.push(Box::new(geometry::Torus::new(
+                    *swept_radius,
+                    *tube_radius,
+                    material(name)?,
+                )));
+            },
+            ObjectDesc::Mesh {
+                path,
+                material: name,
+            } => {
+                let model = obj::Model::load(self.relative_path(path))?;
+                match name {
+                    Some(name) => {
+                        let material = material(name)?;
+                        for group in model.groups {
+                            let material = Arc::clone(&material);
+                            objects
+                                .push(Box::new(TriangleMesh::new(group.mesh, material)));
+                        }
+                    },
+                    None => objects.extend(model.into_geometry()),
+                }
+            },
+            ObjectDesc::Instance { object, transform } => {
+                let transform = transform.iter().try_fold(
+                    Transform::identity(),
+                    |transform, step| -> Result<_, Error> {
+                        Ok(transform.then(self.transform(item, step)?))
+                    },
+                )?;
+
+                let mut instanced = Vec::new();
+                self.object(item, object, materials, &mut instanced)?;
+                for object in instanced {
+                    let object = Arc::<dyn Geometry>::from(object);
+                    objects.push(Box::new(geometry::Instance::new(object, transform)));
+                }
+            },
+            ObjectDesc::Union { objects: parts } => {
+                objects.push(self.csg(item, Operation::Union, parts, materials)?);
+            },
+            ObjectDesc::Intersection { objects: parts } => {
+                objects.push(self.csg(
+                    item,
+                    Operation::Intersection,
+                    parts,
+                    materials,
+                )?);
+            },
+            ObjectDesc::Difference { objects: parts } => {
+                objects.push(self.csg(item, Operation::Difference, parts, materials)?);
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Combines `parts` in order using constructive solid geometry. Parts
+    /// made of several objects, like meshes with many materials, are joined
+    /// into one solid first.
+    fn csg(
+        &self,
+        item: &Spanned<ObjectDesc>,
+        operation: Operation,
+        parts: &[ObjectDesc],
+        materials: &HashMap<&str, Arc<dyn Material>>,
+    ) -> Result<Box<dyn Geometry>, Error> {
+        if parts.len() < 2 {
+            return Err(self.invalid(item, "combinations need at least two objects"));
+        }
+
+        let combine = |operation| {
+            move |a, b| -> Arc<dyn Geometry> { Arc::new(Csg::new(a, b, operation)) }
+        };
+        let mut solids = Vec::new();
+        for part in parts {
+            let mut objects = Vec::new();
+            self.object(item, part, materials, &mut objects)?;
+            let solid = objects
+                .into_iter()
+                .map(Arc::<dyn Geometry>::from)
+                .reduce(combine(Operation::Union))
+                .ok_or_else(|| {
+                    self.invalid(item, "combined objects must not be empty")
+                })?;
+            solids.push(solid);
+        }
+
+        let solid = solids.into_iter().reduce(combine(operation));
+        Ok(Box::new(solid.expect("checked there are parts")))
+    }
+
+    fn transform(
+        &self,
+        item: &Spanned<ObjectDesc>,
+        step: &TransformDesc,
+    ) -> Result<Transform, Error> {
+        Ok(match *step {
+            TransformDesc::Translate(offset) => Transform::translate(vec3(offset)),
+            TransformDesc::Scale(factors) => {
+                if factors.contains(&0.0) {
+                    return Err(self.invalid(item, "scale factors must be non-zero"));
+                }
+                Transform::scale(vec3(factors))
+            },
+            TransformDesc::RotateX(angle) => Transform::rotate_x(angle),
+            TransformDesc::RotateY(angle) => Transform::rotate_y(angle),
+            TransformDesc::RotateZ(angle) => Transform::rotate_z(angle),
+            TransformDesc::Rotate { axis, angle } => {
+                let axis = vec3(axis);
+                if axis.mag() == 0.0 {
+                    return Err(self.invalid(item, "rotation axis must be non-zero"));
+                }
+                Transform::rotate(axis, angle)
+            },
+            TransformDesc::LookAt { eye, centre, up } => {
+                let (eye, centre, up) = (vec3(eye), vec3(centre), vec3(up));
+                if eye == centre || (centre - eye).cross(up).mag() == 0.0 {
+                    let message =
+                        "look_at needs distinct eye and centre, not parallel to up";
+                    return Err(self.invalid(item, message));
+                }
+                Transform::look_at(eye, centre, up)
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a scene with a simple view and sphere, plus `extra`, expecting
+    /// an error, and returns its line number and message.
+    ///
+    /// `extra` starts on line 17 of the scene.
+    fn parse_error(extra: &str) -> (usize, String) {
+        let source = format!(
+            r#"
+[view]
+width = 4
+height = 3
+
+[materials.grey]
+type = "matte"
+ambient = 0.1
+diffuse = 0.8
+colour = [0.5, 0.5, 0.5]
+
+[[objects]]
+type = "sphere"
+centre = [0.0, 0.0, 0.0]
+radius = 1.0
+material = "grey"
+{}"#,
+            extra
+        );
+        let overrides = Overrides::default();
+        match Scene::parse(&source, Path::new("test.toml"), &overrides) {
+            Err(Error::Parse { line, message, .. }) => (line, message),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("parsed invalid scene"),
+        }
+    }
+
+    /// A camera looking at the origin, with the given `kind` and settings.
+    fn camera(kind: &str, settings: &str) -> String {
+        format!(
+            "\n[camera]\ntype = \"{}\"\neye = [0.0, 0.0, 5.0]\n\
+             centre = [0.0, 0.0, 0.0]\n{}\n",
+            kind, settings
+        )
+    }
+
+    /// Follows `extra` with a valid camera.
+    fn with_camera(extra: &str) -> String {
+        format!("{}{}", extra, camera("pinhole", "view_distance = 1.0"))
+    }
+
+    #[test]
+    fn invalid_cameras() {
+        let cases = [
+            (
+                "pinhole",
+                "view_distance = 0.0",
+                "view distance must be positive",
+            ),
+            (
+                "pinhole",
+                "view_distance = 1.0\nzoom = -1.0",
+                "zoom must be positive",
+            ),
+            (
+                "thin-lens",
+                "view_distance = -1.0\nfocal_distance = 5.0\nlens_radius = 0.1",
+                "view distance must be positive",
+            ),
+            (
+                "thin-lens",
+                "view_distance = 1.0\nfocal_distance = 0.0\nlens_radius = 0.1",
+                "focal distance must be positive",
+            ),
+            (
+                "thin-lens",
+                "view_distance = 1.0\nfocal_distance = 5.0\nlens_radius = -0.1",
+                "lens radius must not be negative",
+            ),
+            (
+                "stereo",
+                "view_distance = 1.0\nzoom = 0.0\nseparation = 0.1",
+                "zoom must be positive",
+            ),
+            (
+                "fisheye",
+                "fov = 0.0",
+                "field of view must be in (0, 360] degrees",
+            ),
+            (
+                "fisheye",
+                "fov = 361.0",
+                "field of view must be in (0, 360] degrees",
+            ),
+        ];
+        for (kind, settings, message) in cases {
+            let error = parse_error(&camera(kind, settings));
+            assert_eq!(error, (18, message.to_string()), "{} {}", kind, settings);
+        }
+
+        // valid settings at the limits
+        let source = camera("fisheye", "fov = 360.0");
+        let source = format!("[view]\nwidth = 4\nheight = 3\n{}", source);
+        let overrides = Overrides::default();
+        Scene::parse(&source, Path::new("test.toml"), &overrides).unwrap();
+    }
+
+    #[test]
+    fn negative_material_coefficients() {
+        let extra = r#"
+[materials.shiny]
+type = "phong"
+ambient = 0.1
+diffuse = 0.6
+specular = -0.3
+shininess = 10.0
+colour = [1.0, 1.0, 1.0]
+"#;
+        assert_eq!(
+            parse_error(&with_camera(extra)),
+            (18, "specular must not be negative".to_string())
+        );
+
+        let extra = r#"
+[materials.mirror]
+type = "reflective"
+ambient = 0.1
+diffuse = 0.6
+specular = 0.3
+shininess = 10.0
+colour = [1.0, 1.0, 1.0]
+reflection = -0.5
+"#;
+        assert_eq!(
+            parse_error(&with_camera(extra)),
+            (18, "reflection must not be negative".to_string())
+        );
+    }
+
+    #[test]
+    fn negative_light_intensities() {
+        let extra = r#"
+[[lights]]
+type = "point"
+position = [0.0, 5.0, 0.0]
+
+[[lights]]
+type = "directional"
+direction = [0.0, -1.0, 0.0]
+intensity = -1.0
+"#;
+        assert_eq!(
+            parse_error(&with_camera(extra)),
+            (22, "intensity must not be negative".to_string())
+        );
+
+        let extra = "\n[ambient]\nintensity = -0.5\n";
+        assert_eq!(
+            parse_error(&with_camera(extra)),
+            (18, "intensity must not be negative".to_string())
+        );
+
+        let extra = "\n[environment]\npath = \"missing.hdr\"\nintensity = -2.0\n";
+        assert_eq!(
+            parse_error(&with_camera(extra)),
+            (18, "intensity must not be negative".to_string())
+        );
+    }
+}
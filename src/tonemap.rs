@@ -0,0 +1,189 @@
+//! Turning rendered radiance into displayable colours.
+//!
+//! Rendered colours can be arbitrarily bright, but displays can only show
+//! values between `0` and `1`. Converting a [film](crate::film::Film) for
+//! display goes through a few steps, configured by [`Display`]:
+//!
+//! 1. the colours are scaled by the exposure
+//! 2. a [tone mapping operator](ToneMap) compresses them into the displayable
+//!    range
+//! 3. they're encoded for the display's [gamma](Gamma), using the sRGB
+//!    transfer function by default
+
+use crate::utility::Colour;
+
+use image::Rgb;
+
+/// Operators for compressing high dynamic range colours into `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMap {
+    /// Clamps each channel, so anything too bright is lost.
+    Clamp,
+    /// Reinhard's operator, `L / (1 + L)`, applied to the luminance.
+    ///
+    /// This keeps the hue of every colour, but nothing quite reaches white.
+    Reinhard,
+    /// Reinhard's extended operator, which maps the luminance `white` (and
+    /// anything brighter) to pure white.
+    ReinhardExtended { white: f64 },
+    /// John Hable's filmic curve from Uncharted 2, applied to each channel.
+    Hable,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, applied to each
+    /// channel.
+    Aces,
+}
+
+impl ToneMap {
+    /// Maps a linear colour into the displayable range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raytracer::tonemap::ToneMap;
+    /// use raytracer::utility::Colour;
+    ///
+    /// let bright = Colour::new(2.0, 1.0, 0.5);
+    /// assert_eq!(ToneMap::Clamp.apply(bright), Colour::new(1.0, 1.0, 0.5));
+    ///
+    /// // the ratio between the channels is kept
+    /// let mapped = ToneMap::Reinhard.apply(bright);
+    /// assert!(mapped.r < 1.0);
+    /// assert!((mapped.r / mapped.g - 2.0).abs() < 1e-9);
+    /// ```
+    pub fn apply(self, c: Colour) -> Colour {
+        match self {
+            ToneMap::Clamp => map_channels(c, |x| x),
+            ToneMap::Reinhard => map_luminance(c, |l| l / (1.0 + l)),
+            ToneMap::ReinhardExtended { white } => {
+                map_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            },
+            ToneMap::Hable => {
+                // the curve is calibrated for a fixed exposure bias
+                const WHITE: f64 = 11.2;
+                let scale = 1.0 / hable(WHITE);
+                map_channels(c, |x| hable(2.0 * x) * scale)
+            },
+            ToneMap::Aces => map_channels(c, |x| {
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+        }
+    }
+}
+
+/// The Uncharted 2 filmic curve, before normalisation.
+fn hable(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+/// Applies `f` to each channel, clamping the result into `[0, 1]`.
+fn map_channels(c: Colour, f: impl Fn(f64) -> f64) -> Colour {
+    let f = |x: f64| f(x.max(0.0)).clamp(0.0, 1.0);
+    Colour::new(f(c.r), f(c.g), f(c.b))
+}
+
+/// Scales a colour so that its luminance is mapped by `f`.
+///
+/// Channels may still end up over `1` for saturated colours, so they're
+/// clamped afterwards.
+fn map_luminance(c: Colour, f: impl Fn(f64) -> f64) -> Colour {
+    let luminance = c.luminance();
+    if luminance > 0.0 {
+        map_channels(c * (f(luminance) / luminance), |x| x)
+    } else {
+        Colour::black()
+    }
+}
+
+/// How linear values are encoded for a display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gamma {
+    /// The sRGB transfer function, which most displays and images use.
+    ///
+    /// This is close to a power curve with a gamma of `2.2`, but linear near
+    /// black.
+    Srgb,
+    /// A plain power curve with the given gamma. A gamma of `1` leaves values
+    /// linear.
+    Power(f64),
+}
+
+impl Gamma {
+    /// Encodes a linear value in `[0, 1]` for the display.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use raytracer::tonemap::Gamma;
+    ///
+    /// assert_eq!(Gamma::Power(1.0).encode(0.25), 0.25);
+    /// assert_eq!(Gamma::Power(2.0).encode(0.25), 0.5);
+    ///
+    /// let value = Gamma::Srgb.encode(0.2);
+    /// assert!((Gamma::Srgb.decode(value) - 0.2).abs() < 1e-12);
+    /// ```
+    pub fn encode(self, value: f64) -> f64 {
+        match self {
+            Gamma::Srgb if value <= 0.003_130_8 => 12.92 * value,
+            Gamma::Srgb => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+            Gamma::Power(gamma) => value.powf(1.0 / gamma),
+        }
+    }
+
+    /// The inverse of [`Gamma::encode`], converting a value from the display
+    /// back to linear.
+    pub fn decode(self, value: f64) -> f64 {
+        match self {
+            Gamma::Srgb if value <= 0.040_45 => value / 12.92,
+            Gamma::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Gamma::Power(gamma) => value.powf(gamma),
+        }
+    }
+}
+
+/// Settings for converting rendered colours for display.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::tonemap::{Display, ToneMap};
+/// use raytracer::utility::Colour;
+/// use image::Rgb;
+///
+/// let display = Display {
+///     tone_map: ToneMap::Aces,
+///     exposure: 1.0,
+///     ..Display::default()
+/// };
+/// assert_eq!(display.to_rgb(Colour::black()), Rgb([0, 0, 0]));
+/// assert_eq!(display.to_rgb(Colour::new(100.0, 100.0, 100.0)), Rgb([255, 255, 255]));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    /// The tone mapping operator.
+    pub tone_map: ToneMap,
+    /// Exposure adjustment in stops: each stop doubles the brightness.
+    pub exposure: f64,
+    /// Gamma of the display.
+    pub gamma: Gamma,
+}
+
+impl Default for Display {
+    /// Clamps colours, without any exposure adjustment, for an sRGB display.
+    fn default() -> Self {
+        Self {
+            tone_map: ToneMap::Clamp,
+            exposure: 0.0,
+            gamma: Gamma::Srgb,
+        }
+    }
+}
+
+impl Display {
+    /// Converts a linear colour to an 8-bit colour ready for display.
+    pub fn to_rgb(&self, c: Colour) -> Rgb<u8> {
+        let c = self.tone_map.apply(c * self.exposure.exp2());
+        let channel = |x: f64| (self.gamma.encode(x) * 255.0).round() as u8;
+        Rgb([channel(c.r), channel(c.g), channel(c.b)])
+    }
+}
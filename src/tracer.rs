@@ -1,28 +1,59 @@
 //! Ray tracers using different techniques.
 
-use crate::utility::{Colour, Ray};
+use crate::brdf;
+use crate::sampler::{self, Generator, Samples};
+use crate::utility::{Colour, Ray, Vec3};
 use crate::world::World;
 
+use rand::Rng;
+
+/// Highest chance of a path surviving each round of Russian roulette.
+///
+/// Keeps paths between perfect mirrors from going on forever.
+const MAX_SURVIVAL: f64 = 0.95;
+
 /// An abstract ray tracer.
 ///
 /// Allows using different techniques and methods to get the colour of a ray.
-pub trait Tracer {
+pub trait Tracer: Send + Sync {
     /// Returns the colour of the ray's impact location.
-    fn trace_ray(&self, world: &World, ray: Ray) -> Colour;
+    ///
+    /// `depth` is the number of bounces the ray has taken, starting from `0`
+    /// for rays cast from the camera.
+    fn trace_ray(&self, world: &World, ray: Ray, depth: u32) -> Colour {
+        self.trace_ray_distance(world, ray, depth).0
+    }
+
+    /// Like [`Tracer::trace_ray`], but also returns how far along the ray
+    /// the impact location is, in multiples of its direction. This is `None`
+    /// if the ray escaped without hitting anything.
+    ///
+    /// Materials like [`Dielectric`](crate::material::Dielectric) use the
+    /// distance to absorb light along the way.
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+    ) -> (Colour, Option<f64>);
 }
 
 /// A very simple tracer for a single object.
 ///
-/// If the ray hits the first object in the world, colours the pixel red,
-/// else it colours the pixel black. Can't get any simpler ☺
+/// If the ray hits any object in the world, colours the pixel red, else it
+/// colours the pixel black. Can't get any simpler ☺
 pub struct SimpleTracer {}
 
 impl Tracer for SimpleTracer {
-    fn trace_ray(&self, world: &World, ray: Ray) -> Colour {
-        if let Some(_) = world.objects[0].hit(&ray) {
-            Colour::red()
-        } else {
-            Colour::black()
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        _depth: u32,
+    ) -> (Colour, Option<f64>) {
+        match world.objects.hit(&ray) {
+            Some(hit) => (Colour::red(), Some(hit.t)),
+            None => (Colour::black(), None),
         }
     }
 }
@@ -30,16 +61,360 @@ impl Tracer for SimpleTracer {
 /// A slightly more interesting tracer for multiple objects.
 ///
 /// Uses [`world::World::hit_objects`] to find the closest object in the world.
+/// Only rays from the camera are followed: any secondary rays, like
+/// reflections, are black.
 ///
 /// [`world::World::hit_objects`]: crate::world::World::hit_objects()
 pub struct RayCaster {}
 
 impl Tracer for RayCaster {
-    fn trace_ray(&self, world: &World, ray: Ray) -> Colour {
-        if let Some(hit) = world.hit_objects(ray) {
-            hit.material.shade(&hit)
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+    ) -> (Colour, Option<f64>) {
+        let direction = ray.direction;
+        if depth > 0 {
+            (Colour::black(), None)
+        } else if let Some(hit) = world.hit_objects(ray, self, depth) {
+            (hit.material.shade(&hit), Some(hit.t))
         } else {
-            world.background
+            (world.background_radiance(direction), None)
+        }
+    }
+}
+
+/// A tracer showing only ambient occlusion, for clay renders.
+///
+/// Materials and lights are ignored. Each ray from the camera that hits an
+/// object casts a single ray in a cosine-weighted direction around the normal,
+/// chosen by a sampler: the pixel is white if it escapes, or black if it hits anything
+/// within `max_distance`. Averaged over many samples, this gives every object
+/// the look of white clay under an overcast sky.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::sampler::MultiJittered;
+/// use raytracer::tracer::AmbientOcclusion;
+///
+/// let tracer = AmbientOcclusion::new(f64::INFINITY, &MultiJittered::new(64));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AmbientOcclusion {
+    /// How far away objects can be and still occlude.
+    pub max_distance: f64,
+    samples: Samples<Vec3>,
+}
+
+impl AmbientOcclusion {
+    /// Creates a new tracer, using `sampler` to choose directions.
+    pub fn new<G: Generator + ?Sized>(max_distance: f64, sampler: &G) -> Self {
+        Self {
+            max_distance,
+            samples: sampler.gen_hemisphere_samples(1.0),
         }
     }
 }
+
+impl Tracer for AmbientOcclusion {
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+    ) -> (Colour, Option<f64>) {
+        let direction = ray.direction;
+        if depth > 0 {
+            return (Colour::black(), None);
+        }
+        let hit = match world.hit_objects(ray, self, depth) {
+            Some(hit) => hit,
+            None => return (world.background_radiance(direction), None),
+        };
+
+        let ray = Ray {
+            origin: hit.hit_point,
+            direction: brdf::orient(self.samples.sample(depth), hit.normal),
+        };
+        if world.objects.hit_any(&ray, self.max_distance) {
+            (Colour::black(), Some(hit.t))
+        } else {
+            (Colour::white(), Some(hit.t))
+        }
+    }
+}
+
+/// A recursive tracer, as described by Whitted.
+///
+/// This follows any secondary rays spawned by materials, like mirror
+/// reflections and refractions, up to a maximum depth. Beyond this rays are
+/// just black.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::tracer::Whitted;
+///
+/// // follow up to five bounces after hitting the first object
+/// let tracer = Whitted::new(5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Whitted {
+    /// The deepest level of secondary rays to trace.
+    pub max_depth: u32,
+}
+
+impl Whitted {
+    pub fn new(max_depth: u32) -> Self {
+        Self { max_depth }
+    }
+}
+
+impl Tracer for Whitted {
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+    ) -> (Colour, Option<f64>) {
+        let direction = ray.direction;
+        if depth > self.max_depth {
+            (Colour::black(), None)
+        } else if let Some(hit) = world.hit_objects(ray, self, depth) {
+            (hit.material.shade(&hit), Some(hit.t))
+        } else {
+            (world.background_radiance(direction), None)
+        }
+    }
+}
+
+/// An unbiased Monte Carlo path tracer, for global illumination.
+///
+/// Each ray follows a single random path through the world. At every hit,
+/// light arriving directly from the world's lights is added (next event
+/// estimation), then the material chooses a direction to bounce in, which is
+/// traced recursively. Rays that escape pick up the background, and ambient
+/// light is ignored.
+///
+/// Light emitted by glowing objects or an environment map is only counted
+/// when it's seen by the camera or after a [specular](crate::material::Bounce)
+/// bounce, as otherwise it has already been found by sampling the lights.
+///
+/// After `roulette_depth` bounces paths are randomly terminated using Russian
+/// roulette, with the chance of surviving based on how much light the
+/// material reflects. Survivors are weighted to make up for the others, so
+/// the result stays unbiased and converges with more samples.
+///
+/// Paths never go deeper than `max_depth` bounces, whatever the roulette
+/// says, so that light trapped between mirrors can't recurse forever. Only
+/// the direct light is found at the last hit, which slightly darkens scenes
+/// that reflect most of their light.
+///
+/// # Example
+///
+/// ```
+/// use raytracer::tracer::PathTracer;
+///
+/// // every path gets at least three bounces, and at most ten
+/// let tracer = PathTracer::new(10, 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct PathTracer {
+    /// The deepest level of bounces to trace.
+    pub max_depth: u32,
+    /// Number of bounces before Russian roulette starts.
+    pub roulette_depth: u32,
+}
+
+impl PathTracer {
+    pub fn new(max_depth: u32, roulette_depth: u32) -> Self {
+        Self {
+            max_depth,
+            roulette_depth,
+        }
+    }
+}
+
+impl PathTracer {
+    /// Follows a path, where `count_emitted` is whether light emitted by the
+    /// first thing hit should be added, rather than having been sampled as a
+    /// light already.
+    ///
+    /// Also returns the distance to the first hit, as for
+    /// [`Tracer::trace_ray_distance`].
+    fn trace_path(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+        count_emitted: bool,
+    ) -> (Colour, Option<f64>) {
+        let direction = ray.direction;
+        let hit = match world.hit_objects(ray, self, depth) {
+            Some(hit) => hit,
+            None if count_emitted || world.environment.is_none() => {
+                return (world.background_radiance(direction), None);
+            },
+            None => return (Colour::black(), None),
+        };
+
+        let emitted = if count_emitted && hit.normal.dot(direction) < 0.0 {
+            hit.material.emission()
+        } else {
+            Colour::black()
+        };
+        let direct = emitted + hit.material.direct_shade(&hit);
+        if depth >= self.max_depth {
+            return (direct, Some(hit.t));
+        }
+        let bounce = match hit.material.sample_bounce(&hit) {
+            Some(bounce) => bounce,
+            None => return (direct, Some(hit.t)),
+        };
+
+        let survival = if depth < self.roulette_depth {
+            1.0
+        } else {
+            bounce.weight.max_channel().min(MAX_SURVIVAL)
+        };
+        if sampler::rng().gen::<f64>() >= survival {
+            return (direct, Some(hit.t));
+        }
+
+        let ray = Ray {
+            origin: hit.hit_point,
+            direction: bounce.direction,
+        };
+        let indirect = match self.trace_path(world, ray, depth + 1, bounce.specular) {
+            (light, Some(distance)) => bounce.filter.powf(distance) * light,
+            (light, None) => light,
+        };
+        (direct + bounce.weight * indirect / survival, Some(hit.t))
+    }
+}
+
+impl Tracer for PathTracer {
+    fn trace_ray_distance(
+        &self,
+        world: &World,
+        ray: Ray,
+        depth: u32,
+    ) -> (Colour, Option<f64>) {
+        self.trace_path(world, ray, depth, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::Bvh;
+    use crate::geometry::Sphere;
+    use crate::light::Ambient;
+    use crate::material::{Matte, Phong, Reflective};
+    use crate::sampler::Jittered;
+    use crate::world::ViewPlane;
+
+    /// A diffuse sphere with an albedo of 0.5, lit only by a white
+    /// background, reflects exactly half of the light arriving from every
+    /// direction. Paths are traced from several angles, so any bias in the
+    /// sampled bounces would show up in the average.
+    #[test]
+    fn white_furnace() {
+        let sphere = Sphere {
+            centre: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Matte::new(0.0, 0.5, Colour::white()),
+        };
+        let world = World {
+            background: Colour::white(),
+            environment: None,
+            view: ViewPlane::new(1, 1, 1.0, Jittered::new(1)),
+            objects: Bvh::new(vec![Box::new(sphere)]),
+            ambient: Box::new(Ambient::new(0.0)),
+            lights: Vec::new(),
+        };
+        let tracer = PathTracer::new(20, 3);
+
+        sampler::seed(8);
+        let mut rng = sampler::rng();
+        let num_paths = 20_000;
+        let total = (0..num_paths).fold(0.0, |total, _| {
+            // aim at a random point on the visible half of the sphere
+            let target =
+                Vec3::new(rng.gen_range(-0.7, 0.7), rng.gen_range(-0.7, 0.7), 0.0);
+            let origin = Vec3::new(0.0, 0.0, 5.0);
+            let ray = Ray {
+                origin,
+                direction: (target - origin).normalise(),
+            };
+            let colour = tracer.trace_ray(&world, ray, 0);
+            total + (colour.r + colour.g + colour.b) / 3.0
+        });
+
+        let mean = total / f64::from(num_paths);
+        assert!((mean - 0.5).abs() < 0.005, "mean radiance {}", mean);
+    }
+
+    /// A tinted mirror ball, with nothing to see but a white background.
+    fn mirror_world() -> World {
+        let black = Phong::new(0.0, 0.0, 0.0, 1.0, Colour::white());
+        let mirror = Sphere {
+            centre: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Reflective::new(black, 0.8, Colour::new(1.0, 0.5, 0.25)),
+        };
+        World {
+            background: Colour::white(),
+            environment: None,
+            view: ViewPlane::new(1, 1, 1.0, Jittered::new(1)),
+            objects: Bvh::new(vec![Box::new(mirror)]),
+            ambient: Box::new(Ambient::new(0.0)),
+            lights: Vec::new(),
+        }
+    }
+
+    /// A ray straight at the middle of the [mirror ball](mirror_world).
+    fn head_on() -> Ray {
+        Ray {
+            origin: Vec3::new(0.0, 0.0, 5.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+        }
+    }
+
+    /// Secondary rays are traced up to the maximum depth, and no further.
+    #[test]
+    fn whitted_depth_limit() {
+        let world = mirror_world();
+
+        // the reflection heads straight back out into the background
+        let reflected = Whitted::new(1).trace_ray(&world, head_on(), 0);
+        assert!((reflected.r - 0.8).abs() < 1e-9, "{:?}", reflected);
+        assert!((reflected.g - 0.4).abs() < 1e-9, "{:?}", reflected);
+        assert!((reflected.b - 0.2).abs() < 1e-9, "{:?}", reflected);
+
+        let limited = Whitted::new(0).trace_ray(&world, head_on(), 0);
+        assert_eq!(limited, Colour::black());
+        let limited = Whitted::new(3).trace_ray(&world, head_on(), 4);
+        assert_eq!(limited, Colour::black());
+    }
+
+    /// Paths stop bouncing at the maximum depth, even before Russian roulette
+    /// starts, leaving only the direct light.
+    #[test]
+    fn path_depth_limit() {
+        let world = mirror_world();
+        for _ in 0..100 {
+            let limited = PathTracer::new(0, 3).trace_ray(&world, head_on(), 0);
+            assert_eq!(limited, Colour::black());
+            let limited = PathTracer::new(2, 3).trace_ray(&world, head_on(), 2);
+            assert_eq!(limited, Colour::black());
+        }
+
+        let bounced = (0..100).fold(Colour::black(), |total, _| {
+            total + PathTracer::new(1, 3).trace_ray(&world, head_on(), 0)
+        });
+        assert!(bounced.r > 0.0);
+    }
+}
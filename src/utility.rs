@@ -1,7 +1,6 @@
 //! Various helper utilities used in the raytracer
 
-use image::Rgb;
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
 /// A three-dimensional vector.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -27,7 +26,7 @@ impl Vec3 {
         Self {
             x: self.y * rhs.z - self.z * rhs.y,
             y: self.z * rhs.x - self.x * rhs.z,
-            z: self.z * rhs.y - self.y * rhs.x,
+            z: self.x * rhs.y - self.y * rhs.x,
         }
     }
 
@@ -40,6 +39,31 @@ impl Vec3 {
     pub fn normalise(self) -> Self {
         self / self.mag()
     }
+
+    /// Component-wise minimum of two vectors.
+    pub fn min(self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    /// Component-wise maximum of two vectors.
+    pub fn max(self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+}
+
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    /// Access the components of the vector by axis: `0` is `x`, `1` is `y`,
+    /// and `2` is `z`.
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis {} out of range for Vec3", axis),
+        }
+    }
 }
 
 impl Sub for Vec3 {
@@ -206,6 +230,16 @@ impl Colour {
         Self::new(0.0, 0.0, 1.0)
     }
 
+    /// The relative luminance of the colour, using the Rec. 709 primaries.
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    /// The value of the brightest channel.
+    pub fn max_channel(self) -> f64 {
+        self.r.max(self.g).max(self.b)
+    }
+
     /// Component-wise floating-point power function
     pub fn powf(self, n: f64) -> Self {
         Self::new(self.r.powf(n), self.g.powf(n), self.b.powf(n))
@@ -294,21 +328,346 @@ impl Div<Colour> for f64 {
     }
 }
 
-impl From<Colour> for Rgb<u8> {
-    fn from(c: Colour) -> Rgb<u8> {
-        let max = c.r.max(c.g.max(c.b));
-        let c = if max > 1.0 { c / max } else { c };
-        Rgb([
-            (c.r * 255.0).min(255.0).max(0.0) as u8,
-            (c.g * 255.0).min(255.0).max(0.0) as u8,
-            (c.b * 255.0).min(255.0).max(0.0) as u8,
-        ])
-    }
-}
-
 /// An infinite ray, from a given point and with a given direction.
 #[derive(Debug, Clone)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
 }
+
+impl Ray {
+    /// The point at distance `t` along the ray, in multiples of its direction.
+    pub fn at(&self, t: f64) -> Vec3 {
+        self.origin + t * self.direction
+    }
+}
+
+/// A 4x4 matrix, in row-major order.
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// An affine transformation, like a translation, rotation, or scale.
+///
+/// This is a 4x4 matrix acting on homogeneous coordinates, kept along with
+/// its inverse. Transformations are combined with `*`, where `a * b` applies
+/// `b` first and then `a`, or equivalently with [`then`](Transform::then).
+///
+/// # Example
+///
+/// ```
+/// use raytracer::utility::{Transform, Vec3};
+///
+/// // a quarter turn about `y`, then up by two
+/// let transform = Transform::rotate_y(90.0).then(Transform::translate(Vec3::new(0.0, 2.0, 0.0)));
+///
+/// let point = transform.transform_point(Vec3::new(1.0, 0.0, 0.0));
+/// assert!((point - Vec3::new(0.0, 2.0, -1.0)).mag() < 1e-9);
+///
+/// let back = transform.inverse().transform_point(point);
+/// assert!((back - Vec3::new(1.0, 0.0, 0.0)).mag() < 1e-9);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    /// The transformation which leaves everything where it is.
+    pub fn identity() -> Self {
+        Self {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    /// Moves everything by `offset`.
+    pub fn translate(offset: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Scales everything about the origin by a factor along each axis.
+    ///
+    /// The factors must be non-zero; negative factors mirror along that axis.
+    pub fn scale(factors: Vec3) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][axis] = factors[axis];
+            inverse[axis][axis] = 1.0 / factors[axis];
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Rotates about the `x` axis by an angle in degrees.
+    pub fn rotate_x(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    /// Rotates about the `y` axis by an angle in degrees.
+    pub fn rotate_y(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    /// Rotates about the `z` axis by an angle in degrees.
+    pub fn rotate_z(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    /// Rotates about an axis through the origin by an angle in degrees.
+    ///
+    /// Positive angles turn anticlockwise when looking back along the axis
+    /// towards the origin. The axis must be non-zero, but needn't be
+    /// normalised.
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let Vec3 { x, y, z } = axis.normalise();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos;
+
+        let rotation = [
+            [x * x * k + cos, x * y * k - z * sin, x * z * k + y * sin],
+            [x * y * k + z * sin, y * y * k + cos, y * z * k - x * sin],
+            [x * z * k - y * sin, y * z * k + x * sin, z * z * k + cos],
+        ];
+        // rotations are orthogonal, so the inverse is the transpose
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for row in 0..3 {
+            for col in 0..3 {
+                matrix[row][col] = rotation[row][col];
+                inverse[col][row] = rotation[row][col];
+            }
+        }
+        Self { matrix, inverse }
+    }
+
+    /// Places an object at `eye`, facing towards `centre`.
+    ///
+    /// The object's `-z` axis is turned to point at `centre`, and its `y` axis
+    /// is turned to be as close to `up` as possible, as for a
+    /// [camera](crate::camera::Location). `up` must not be parallel to the
+    /// direction the object faces.
+    pub fn look_at(eye: Vec3, centre: Vec3, up: Vec3) -> Self {
+        let w = (eye - centre).normalise();
+        let u = up.cross(w).normalise();
+        let v = w.cross(u);
+
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for (col, basis) in [u, v, w].iter().enumerate() {
+            for row in 0..3 {
+                matrix[row][col] = basis[row];
+                inverse[col][row] = basis[row];
+            }
+            matrix[col][3] = eye[col];
+            inverse[col][3] = -basis.dot(eye);
+        }
+        Self { matrix, inverse }
+    }
+
+    /// The transformation which undoes this one.
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// Applies this transformation, followed by `next`.
+    pub fn then(self, next: Self) -> Self {
+        next * self
+    }
+
+    /// The determinant of the linear part of the transformation: how much it
+    /// scales volumes by, and negative if it mirrors them.
+    pub fn determinant(&self) -> f64 {
+        let m = &self.matrix;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Transforms a point, which is affected by every part of the
+    /// transformation.
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        apply(&self.matrix, p, 1.0)
+    }
+
+    /// Transforms a direction or offset, which isn't affected by translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        apply(&self.matrix, v, 0.0)
+    }
+
+    /// Transforms a surface normal, so that it stays perpendicular to the
+    /// transformed surface.
+    ///
+    /// This uses the transpose of the inverse, and the result isn't
+    /// normalised.
+    pub fn transform_normal(&self, n: Vec3) -> Vec3 {
+        let m = &self.inverse;
+        Vec3::new(
+            m[0][0] * n.x + m[1][0] * n.y + m[2][0] * n.z,
+            m[0][1] * n.x + m[1][1] * n.y + m[2][1] * n.z,
+            m[0][2] * n.x + m[1][2] * n.y + m[2][2] * n.z,
+        )
+    }
+
+    /// Transforms a ray.
+    ///
+    /// The direction isn't normalised, so distances along the ray, in
+    /// multiples of its direction, are the same before and after.
+    pub fn transform_ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.transform_point(ray.origin),
+            direction: self.transform_vector(ray.direction),
+        }
+    }
+}
+
+/// Multiplies a matrix by the homogeneous vector `(v, w)`, ignoring the
+/// bottom row, which is always `(0, 0, 0, 1)` for affine transformations.
+fn apply(m: &Matrix, v: Vec3, w: f64) -> Vec3 {
+    let row = |r: &[f64; 4]| r[0] * v.x + r[1] * v.y + r[2] * v.z + r[3] * w;
+    Vec3::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+/// Multiplies two matrices.
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 4];
+    for (row, product_row) in product.iter_mut().enumerate() {
+        for (col, entry) in product_row.iter_mut().enumerate() {
+            *entry = (0..4).map(|i| a[row][i] * b[i][col]).sum();
+        }
+    }
+    product
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    /// Combines two transformations, applying `rhs` first.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).mag() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    /// A mix of every kind of transformation.
+    fn transforms() -> Vec<Transform> {
+        vec![
+            Transform::translate(Vec3::new(1.0, -2.0, 3.0)),
+            Transform::scale(Vec3::new(2.0, 0.5, -3.0)),
+            Transform::rotate(Vec3::new(1.0, 2.0, -1.0), 33.0),
+            Transform::look_at(
+                Vec3::new(4.0, 1.0, -2.0),
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+            Transform::rotate_x(70.0)
+                .then(Transform::scale(Vec3::new(1.0, 4.0, 0.25)))
+                .then(Transform::translate(Vec3::new(0.0, 0.0, -5.0))),
+        ]
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let point = Vec3::new(0.3, -1.7, 2.2);
+        for transform in transforms() {
+            let inverse = transform.inverse();
+            assert_close(
+                inverse.transform_point(transform.transform_point(point)),
+                point,
+            );
+            assert_close(
+                inverse.transform_vector(transform.transform_vector(point)),
+                point,
+            );
+            // the product of the two is the identity
+            assert_close((transform * inverse).transform_point(point), point);
+            assert_close(transform.then(inverse).transform_point(point), point);
+        }
+    }
+
+    #[test]
+    fn then_applies_in_order() {
+        let turn = Transform::rotate_z(90.0);
+        let shift = Transform::translate(Vec3::new(1.0, 0.0, 0.0));
+        let point = Vec3::new(1.0, 0.0, 0.0);
+
+        // anticlockwise looking down `z`, so `x` turns to `y`
+        assert_close(turn.transform_point(point), Vec3::new(0.0, 1.0, 0.0));
+        assert_close(
+            turn.then(shift).transform_point(point),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+        assert_close(
+            shift.then(turn).transform_point(point),
+            Vec3::new(0.0, 2.0, 0.0),
+        );
+        assert_close(
+            (shift * turn).transform_point(point),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        // a tilted plane, with two directions along it
+        let normal = Vec3::new(1.0, 1.0, 1.0);
+        let along = [Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, -1.0)];
+        for transform in transforms() {
+            let normal = transform.transform_normal(normal);
+            for &v in &along {
+                let v = transform.transform_vector(v);
+                assert!(normal.dot(v).abs() < 1e-9, "{:?}", transform);
+            }
+        }
+
+        // stretching a plane along its normal doesn't tilt it
+        let stretch = Transform::scale(Vec3::new(1.0, 5.0, 1.0));
+        let up = stretch.transform_normal(Vec3::new(0.0, 1.0, 0.0));
+        assert_close(up.normalise(), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn determinant() {
+        let scale = Transform::scale(Vec3::new(2.0, 0.5, -3.0));
+        assert!((scale.determinant() + 3.0).abs() < 1e-9);
+        let turn = Transform::rotate(Vec3::new(1.0, 2.0, -1.0), 33.0);
+        assert!((turn.determinant() - 1.0).abs() < 1e-9);
+        assert!((turn.then(scale).determinant() + 3.0).abs() < 1e-9);
+    }
+}
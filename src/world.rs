@@ -1,11 +1,16 @@
 //! The world to render.
 
-use crate::geometry::Geometry;
+use crate::bvh::Bvh;
+use crate::environment::EnvironmentMap;
 use crate::light::Light;
 use crate::material::Material;
 use crate::sampler;
+use crate::tonemap::Display;
+use crate::tracer::Tracer;
 use crate::utility::{Colour, Ray, Vec3};
 
+use std::sync::Arc;
+
 /// General information about the view.
 ///
 /// Includes things like the position and scale of viewing, and other
@@ -19,8 +24,9 @@ pub struct ViewPlane {
     pub vres: u32,
     /// The size of a pixel in the image; the scaling factor.
     pub s: f64,
-    /// Gamma correction to apply. (Currently unused.)
-    pub gamma: f64,
+    /// How to convert the rendered colours for display, including tone
+    /// mapping and gamma correction.
+    pub display: Display,
     /// Sampler for antialiasing
     pub sampler: Box<dyn sampler::Generator>,
 }
@@ -34,7 +40,7 @@ impl ViewPlane {
             hres,
             vres,
             s,
-            gamma: 1.0,
+            display: Display::default(),
             sampler: Box::new(sampler),
         }
     }
@@ -42,44 +48,81 @@ impl ViewPlane {
 
 pub struct Intersection<'m, 'w> {
     pub ray: Ray,
+    /// How far along the ray the hit is, in multiples of its direction.
+    pub t: f64,
     pub hit_point: Vec3,
     pub normal: Vec3,
-    pub depth: i32,
+    /// How many bounces the ray has taken from the camera; `0` for primary
+    /// rays.
+    pub depth: u32,
     pub material: &'m dyn Material,
     pub world: &'w World,
+    /// The tracer which cast the ray, used to trace any secondary rays.
+    pub tracer: &'w dyn Tracer,
+}
+
+impl Intersection<'_, '_> {
+    /// Traces a secondary ray spawned from this intersection, like a
+    /// reflection, one level deeper than this one.
+    pub fn trace(&self, ray: Ray) -> Colour {
+        self.tracer.trace_ray(self.world, ray, self.depth + 1)
+    }
+
+    /// Like [`Intersection::trace`], but also returns how far the ray went,
+    /// as for [`Tracer::trace_ray_distance`].
+    pub fn trace_distance(&self, ray: Ray) -> (Colour, Option<f64>) {
+        self.tracer
+            .trace_ray_distance(self.world, ray, self.depth + 1)
+    }
 }
 
 /// The world itself.
 #[derive(Debug)]
 pub struct World {
+    /// The colour seen by rays that don't hit anything, if there's no
+    /// environment map.
     pub background: Colour,
+    /// An image surrounding the world, seen by rays that don't hit anything.
+    ///
+    /// To light the world, it should also be added as an
+    /// [`EnvironmentLight`](crate::light::EnvironmentLight).
+    pub environment: Option<Arc<EnvironmentMap>>,
     pub view: ViewPlane,
-    pub objects: Vec<Box<dyn Geometry>>,
+    pub objects: Bvh,
     pub ambient: Box<dyn Light>,
     pub lights: Vec<Box<dyn Light>>,
 }
 
 impl World {
     /// Returns the intersection for the first object hit by the given ray.
-    pub fn hit_objects(&self, ray: Ray) -> Option<Intersection> {
-        let nearest = self
-            .objects
-            .iter()
-            .filter_map(|obj| obj.hit(&ray))
-            .min_by(|a, b| a.0.partial_cmp(&b.0).expect("distance is NaN"));
+    ///
+    /// The `tracer` and `depth` are those of the ray, and are passed along
+    /// through the intersection for use when shading.
+    pub fn hit_objects<'w>(
+        &'w self,
+        ray: Ray,
+        tracer: &'w dyn Tracer,
+        depth: u32,
+    ) -> Option<Intersection<'w, 'w>> {
+        let hit = self.objects.hit(&ray)?;
+        Some(Intersection {
+            hit_point: ray.at(hit.t),
+            t: hit.t,
+            ray,
+            depth,
+            normal: hit.normal,
+            material: hit.material,
+            world: self,
+            tracer,
+        })
+    }
 
-        if let Some((t, g)) = nearest {
-            let hit_point = ray.origin + t * ray.direction;
-            Some(Intersection {
-                ray,
-                hit_point,
-                depth: 0,
-                normal: g.normal(hit_point),
-                material: g.material(),
-                world: self,
-            })
-        } else {
-            None
+    /// The radiance arriving from the given direction, for rays that don't
+    /// hit anything.
+    pub fn background_radiance(&self, direction: Vec3) -> Colour {
+        match &self.environment {
+            Some(map) => map.radiance(direction.normalise()),
+            None => self.background,
         }
     }
 }